@@ -1,25 +1,71 @@
+use std::path::PathBuf;
+
 use console::{Style};
-use rusqlite::{Connection};
+use rusqlite::{Connection, OpenFlags};
 use dialoguer::{theme::ColorfulTheme};
 
+use crate::config::{Config, default_config_path};
+use crate::encryption;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::sql::SqlStyle;
+
 pub struct AppView {
     pub dialog_theme: ColorfulTheme,
+    pub sql_style: SqlStyle,
 }
 
 impl AppView {
-    fn new() -> Self {
+    fn new(config: &Config) -> Self {
         Self {
             dialog_theme: ColorfulTheme {
-                values_style: Style::new().yellow().dim(),
-                indicator_style: Style::new().yellow().bold(),
-                yes_style: Style::new().yellow().dim(),
-                no_style: Style::new().yellow().dim(),
+                values_style: Style::from_dotted_str(&config.theme.values),
+                indicator_style: Style::from_dotted_str(&config.theme.indicator),
+                yes_style: Style::from_dotted_str(&config.theme.yes),
+                no_style: Style::from_dotted_str(&config.theme.no),
                 ..ColorfulTheme::default()
             },
+            sql_style: SqlStyle::new(&config.theme),
         }
     }
 }
 
+/// How a database file is opened, set from the command line flags.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct OpenMode {
+    pub read_only: bool,
+    // Fail instead of creating an empty database when the file is missing
+    pub no_create: bool,
+    // Promise that nobody changes the file while it is open, sqlite skips locking entirely
+    pub immutable: bool,
+}
+
+impl OpenMode {
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.immutable
+    }
+
+    fn flags(&self) -> OpenFlags {
+        let mut flags = OpenFlags::SQLITE_OPEN_NO_MUTEX;
+
+        // Only the URI built for immutable files is read as one, other paths like "file:a.db" are names
+        if self.immutable {
+            flags |= OpenFlags::SQLITE_OPEN_URI;
+        }
+
+        if self.is_read_only() {
+            flags |= OpenFlags::SQLITE_OPEN_READ_ONLY;
+        } else {
+            flags |= OpenFlags::SQLITE_OPEN_READ_WRITE;
+            if !self.no_create {
+                flags |= OpenFlags::SQLITE_OPEN_CREATE;
+            }
+        }
+
+        flags
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum SqliteConnection {
     File(String),
@@ -28,33 +74,116 @@ enum SqliteConnection {
 
 pub struct App {
     pub view: AppView,
+    pub config: Config,
+    pub config_path: PathBuf,
     pub connection: Option<Connection>,
+    pub history: Option<History>,
     connection_type: SqliteConnection,
+    open_mode: OpenMode,
+    encrypted: bool,
     active_table: Option<String>,
 }
 
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl App {
     pub fn new() -> Self {
+        Self::with_config(Config::default(), default_config_path())
+    }
+
+    pub fn with_config(config: Config, config_path: PathBuf) -> Self {
         App {
-            view: AppView::new(),
+            view: AppView::new(&config),
+            config,
+            config_path,
             connection: None,
+            history: None,
             connection_type: SqliteConnection::Memory,
+            open_mode: OpenMode::default(),
+            encrypted: false,
             active_table: None,
         }
     }
 
-    pub fn connect_in_file(&mut self, path: &str) -> rusqlite::Result<()> {
+    // Rebuilds everything derived from the config after it was changed
+    pub fn apply_config(&mut self) {
+        self.view = AppView::new(&self.config);
+    }
+
+    /// `key` unlocks a database encrypted with SQLCipher.
+    pub fn connect_in_file(&mut self, path: &str, mode: OpenMode, key: Option<&str>) -> Result<()> {
+        // Read-only opening fails on a missing file as well, only with a less helpful message
+        if (mode.no_create || mode.is_read_only()) && !std::path::Path::new(path).exists() {
+            return Err(Error::validation(format!("Database file '{}' does not exist", path)));
+        }
+
+        // Immutable is only available as an URI parameter
+        let connection = if mode.immutable {
+            Connection::open_with_flags(format!("file:{}?immutable=1", escape_uri_path(path)), mode.flags())?
+        } else if path.starts_with("file:") {
+            // sqlite built with SQLITE_USE_URI reads such names as URIs even without the flag
+            Connection::open_with_flags(format!("./{}", path), mode.flags())?
+        } else {
+            Connection::open_with_flags(path, mode.flags())?
+        };
+
+        match key {
+            Some(key) => encryption::set_key(&connection, key)?,
+            // Otherwise the first query fails with "file is not a database"
+            None if !encryption::is_supported() && encryption::looks_encrypted(path) => {
+                return Err(Error::validation(format!("'{}' is encrypted or is not an SQLite database. {}", path, encryption::UNSUPPORTED)));
+            },
+            None => {},
+        }
+
         self.connection_type = SqliteConnection::File(String::from(path));
-        self.connection = Some(Connection::open(path)?);
+        self.open_mode = mode;
+        self.encrypted = key.is_some();
+        self.connection = Some(connection);
         Ok(())
     }
 
     pub fn connect_in_memory(&mut self) -> rusqlite::Result<()> {
         self.connection_type = SqliteConnection::Memory;
+        self.open_mode = OpenMode::default();
+        self.encrypted = false;
         self.connection = Some(Connection::open_in_memory()?);
         Ok(())
     }
 
+    pub fn open_mode(&self) -> OpenMode {
+        self.open_mode
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.open_mode.is_read_only()
+    }
+
+    // Screens which change the database call this first, so the user is told why before any prompt
+    pub fn require_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(Error::validation("Database is opened in read-only mode"));
+        }
+        Ok(())
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Size of the database file, or of the pages in use for in-memory databases.
+    /// Changes in a WAL file are not counted until they are checkpointed.
+    pub fn database_size(&self) -> Result<u64> {
+        match self.path() {
+            Some(path) => Ok(std::fs::metadata(path)?.len()),
+            None => Ok(crate::db::storage_info(self.require_connection()?)?.size()),
+        }
+    }
+
     pub fn is_in_memory(&self) -> bool {
         self.connection_type == SqliteConnection::Memory
     }
@@ -73,33 +202,26 @@ impl App {
         }
     }
 
-    pub fn set_active_table(&mut self, text: &str) -> Result<(), String> {
-        use rusqlite::params;
+    // For screens which cannot do anything without an open database or a chosen table
+    pub fn require_connection(&self) -> Result<&Connection> {
+        self.connection.as_ref().ok_or_else(|| Error::validation("No connection is set to sqlite"))
+    }
 
-        let connection = if let Some(c) = &self.connection {
-            c
-        } else {
-            return Err(format!("Connection is not set"));
-        };
+    pub fn require_connection_mut(&mut self) -> Result<&mut Connection> {
+        self.connection.as_mut().ok_or_else(|| Error::validation("No connection is set to sqlite"))
+    }
 
-        if !crate::utils::validate_table_name(text) {
-            return Err(format!("Table name is not alphanumeric"));
-        }
+    pub fn require_active_table(&self) -> Result<&str> {
+        self.active_table().ok_or_else(|| Error::validation("No active table selected"))
+    }
 
-        match connection.prepare(
-            format!("SELECT name FROM sqlite_master WHERE type='table' AND name='{}'", text).as_str(),
-        ) {
-            Ok(mut statement) => {
-                match statement.exists(params![]) {
-                    Ok(exists) => {
-                        if !exists {
-                            return Err(format!("Table does not exists."));
-                        }
-                    },
-                    Err(err) => { return Err(format!("{}", err)); }
-                }
-            }
-            Err(err) => { return Err(format!("{}", err)); }
+    pub fn set_active_table(&mut self, text: &str) -> Result<()> {
+        let connection = self.require_connection()?;
+
+        if !crate::db::table_exists(connection, text)? {
+            let names = crate::db::table_names(connection)?;
+            let suggestion = crate::utils::did_you_mean(text, names.iter().map(String::as_str));
+            return Err(Error::validation(format!("Table '{}' does not exist.{}", text, suggestion)));
         }
 
         // Successfuly found out that table exists
@@ -107,3 +229,13 @@ impl App {
         Ok(())
     }
 }
+
+// Characters with a meaning in URIs, sqlite decodes %XX escapes in the path
+fn escape_uri_path(path: &str) -> String {
+    path.chars()
+        .map(|c| match c {
+            '%' | '?' | '#' => format!("%{:02X}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+// BLOB cells and files. Contents are streamed through sqlite's incremental blob API, so large files
+// are never held in memory. Types are recognized by the magic bytes at the start of the data.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use rusqlite::{DatabaseName, Connection, params};
+
+use crate::db::quote_identifier;
+use crate::error::{Error, Result};
+use crate::utils::format_size;
+
+/// Kind of data found in a blob.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileType {
+    pub name: &'static str,
+    pub mime: &'static str,
+    pub extension: &'static str,
+}
+
+impl FileType {
+    const fn new(name: &'static str, mime: &'static str, extension: &'static str) -> Self {
+        Self { name, mime, extension }
+    }
+}
+
+const UNKNOWN: FileType = FileType::new("blob", "application/octet-stream", "bin");
+const TEXT: FileType = FileType::new("text", "text/plain", "txt");
+
+// Magic bytes at the given offset
+const SIGNATURES: [(usize, &[u8], FileType); 22] = [
+    (0, b"\x89PNG\r\n\x1a\n", FileType::new("png", "image/png", "png")),
+    (0, b"\xff\xd8\xff", FileType::new("jpeg", "image/jpeg", "jpg")),
+    (0, b"GIF87a", FileType::new("gif", "image/gif", "gif")),
+    (0, b"GIF89a", FileType::new("gif", "image/gif", "gif")),
+    (8, b"WEBP", FileType::new("webp", "image/webp", "webp")),
+    (0, b"II*\0", FileType::new("tiff", "image/tiff", "tiff")),
+    (0, b"MM\0*", FileType::new("tiff", "image/tiff", "tiff")),
+    (0, b"\0\0\x01\0", FileType::new("ico", "image/vnd.microsoft.icon", "ico")),
+    (0, b"BM", FileType::new("bmp", "image/bmp", "bmp")),
+    (0, b"%PDF-", FileType::new("pdf", "application/pdf", "pdf")),
+    (0, b"PK\x03\x04", FileType::new("zip", "application/zip", "zip")),
+    (0, b"\x1f\x8b", FileType::new("gzip", "application/gzip", "gz")),
+    (0, b"BZh", FileType::new("bzip2", "application/x-bzip2", "bz2")),
+    (0, b"\xfd7zXZ\0", FileType::new("xz", "application/x-xz", "xz")),
+    (0, b"7z\xbc\xaf\x27\x1c", FileType::new("7z", "application/x-7z-compressed", "7z")),
+    (0, b"SQLite format 3\0", FileType::new("sqlite", "application/vnd.sqlite3", "db")),
+    (8, b"WAVE", FileType::new("wav", "audio/wav", "wav")),
+    (0, b"ID3", FileType::new("mp3", "audio/mpeg", "mp3")),
+    (0, b"OggS", FileType::new("ogg", "audio/ogg", "ogg")),
+    (0, b"fLaC", FileType::new("flac", "audio/flac", "flac")),
+    (4, b"ftyp", FileType::new("mp4", "video/mp4", "mp4")),
+    (0, b"\x7fELF", FileType::new("elf", "application/x-executable", "elf")),
+];
+
+/// Guesses the type from the first bytes, data which is not recognized is text when it is valid
+/// UTF-8 without control characters.
+pub fn sniff(bytes: &[u8]) -> FileType {
+    let signature = SIGNATURES.iter()
+        .find(|(offset, magic, _)| bytes.get(*offset..offset + magic.len()) == Some(*magic));
+    if let Some((_, _, file_type)) = signature {
+        return *file_type;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.is_empty() && !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => TEXT,
+        _ => UNKNOWN,
+    }
+}
+
+/// Short description for the grid, like `<png 24.0 KiB>`.
+pub fn describe(bytes: &[u8]) -> String {
+    format!("<{} {}>", sniff(bytes).name, format_size(bytes.len() as u64))
+}
+
+/// Replaces the cell with the contents of `path`. The cell is resized with zeroblob() first, the
+/// incremental blob API can't change the size. Returns the number of bytes written.
+pub fn load_file(connection: &Connection, table: &str, column: &str, rowid: i64, path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    // Savepoint works inside a transaction opened by the user as well as without one
+    connection.execute_batch("SAVEPOINT load_blob")?;
+
+    let result = (|| -> Result<()> {
+        let sql = format!("UPDATE {} SET {} = zeroblob(?) WHERE rowid = ?", quote_identifier(table), quote_identifier(column));
+        connection.execute(&sql, params![size as i64, rowid]).map_err(|err| Error::in_statement(err, &sql))?;
+
+        let mut blob = connection.blob_open(DatabaseName::Main, table, column, rowid, false)?;
+        let written = io::copy(&mut file, &mut blob)?;
+        if written != size {
+            return Err(Error::validation(format!("File '{}' changed while it was read", path.display())));
+        }
+
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => connection.execute_batch("RELEASE load_blob")?,
+        Err(err) => {
+            let _ = connection.execute_batch("ROLLBACK TO load_blob; RELEASE load_blob");
+            return Err(err);
+        },
+    }
+
+    Ok(size)
+}
+
+/// Writes the cell to `path`, an existing file is overwritten. Returns the number of bytes written.
+pub fn save_file(connection: &Connection, table: &str, column: &str, rowid: i64, path: &Path) -> Result<u64> {
+    let mut blob = connection.blob_open(DatabaseName::Main, table, column, rowid, true)?;
+    let mut file = File::create(path)?;
+
+    Ok(io::copy(&mut blob, &mut file)?)
+}
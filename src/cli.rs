@@ -0,0 +1,464 @@
+// Command line of the `sqlite` binary. Subcommands run a single action and exit, without them
+// the interactive menus are shown.
+
+use std::io::Write;
+
+use regex::Regex;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::app::App;
+use crate::commands;
+use crate::db;
+use crate::error::Result;
+use crate::migrations;
+
+const BIN_NAME: &str = "sqlite";
+const VERSION: &str = "0.1.0";
+// Help is wrapped by clap, the man page keeps its lines as they are
+const MAN_WIDTH: usize = 80;
+
+/// Flags and subcommands of the `sqlite` binary.
+pub fn build<'a, 'b>() -> clap::App<'a, 'b> {
+    use clap::*;
+    App::new("Sqlite demonstration")
+        .bin_name(BIN_NAME)
+        .version(VERSION)
+        .author("Karol Milewczyk <kmilewczyk96@gmail.com>")
+        .about("Implementation of uni assignment. It is interactive demonstration of sqlite basic features")
+        .arg(Arg::with_name("path")
+            .short("p")
+            .long("path")
+            .takes_value(true)
+            .help("Forces sqlite to work on a file in the specified path"))
+        .arg(Arg::with_name("config")
+            .long("config")
+            .takes_value(true)
+            .value_name("file")
+            .help("Reads settings from the file instead of $XDG_CONFIG_HOME/sqlite-cli-demo/config.toml"))
+        .arg(Arg::with_name("dump")
+            .long("dump")
+            .takes_value(true)
+            .min_values(0)
+            .value_name("file")
+            .help("Writes SQL dump of the database into the file or to stdout and exits"))
+        .arg(Arg::with_name("diff")
+            .long("diff")
+            .takes_value(true)
+            .value_name("other.db")
+            .help("Prints how the schema of the other database differs and exits"))
+        .arg(Arg::with_name("migration")
+            .long("migration")
+            .requires("diff")
+            .help("With --diff prints SQL which brings the database in line with the other one instead"))
+        .arg(Arg::with_name("readonly")
+            .long("readonly")
+            .requires("path")
+            .help("Opens the database read-only, options which change it are hidden"))
+        .arg(Arg::with_name("no-create")
+            .long("no-create")
+            .requires("path")
+            .help("Fails when the database file does not exist instead of creating it"))
+        .arg(Arg::with_name("immutable")
+            .long("immutable")
+            .requires("path")
+            .help("Opens the file read-only without locking, for files which nothing else changes, like snapshots on read-only media"))
+        .arg(Arg::with_name("key")
+            .long("key")
+            .takes_value(true)
+            .min_values(0)
+            .requires("path")
+            .value_name("key")
+            .help("Opens a database encrypted with SQLCipher, asks for the key when it is not given. Needs the sqlcipher feature"))
+        .arg(Arg::with_name("tui")
+            .long("tui")
+            .help("Opens a full-screen grid of the tables instead of the menus"))
+        .global_setting(AppSettings::VersionlessSubcommands)
+        .subcommands(subcommands())
+        // Called by the completion scripts
+        .subcommand(SubCommand::with_name("complete-names")
+            .setting(AppSettings::Hidden)
+            .arg(Arg::with_name("kind")
+                .required(true)
+                .possible_values(&["tables", "columns"]))
+            .arg(Arg::with_name("table")))
+}
+
+/// Subcommands listed in the help and the man page.
+fn subcommands<'a, 'b>() -> Vec<clap::App<'a, 'b>> {
+    use clap::*;
+    vec![
+        SubCommand::with_name("tables")
+            .about("Lists tables"),
+        SubCommand::with_name("schema")
+            .about("Lists columns of a table")
+            .arg(Arg::with_name("table").required(true).help("Name of the table")),
+        SubCommand::with_name("create-table")
+            .about("Creates a table")
+            .arg(Arg::with_name("table").required(true).help("Name of the table"))
+            .arg(Arg::with_name("column")
+                .long("column")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+                .value_name("name:type[=default]")
+                .help("Column of the table, repeat for every column")),
+        SubCommand::with_name("insert")
+            .about("Inserts a row, columns which are not given get their default")
+            .arg(Arg::with_name("table").required(true).help("Name of the table"))
+            .arg(Arg::with_name("values")
+                .multiple(true)
+                .required(true)
+                .value_name("column=value")
+                .help("Values read like in the insert prompt: NULL, 'quoted text', x:<hex> or @<file> for a blob")),
+        SubCommand::with_name("select")
+            .about("Prints rows of a table")
+            .arg(Arg::with_name("table").required(true).help("Name of the table"))
+            .arg(Arg::with_name("sort")
+                .long("sort")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("column[:asc|:desc]")
+                .help("Sorts by the column, repeat to sort by more columns"))
+            .arg(Arg::with_name("where")
+                .long("where")
+                .takes_value(true)
+                .value_name("condition")
+                .help("SQL condition rows have to meet"))
+            .arg(Arg::with_name("limit")
+                .long("limit")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_number)
+                .help("Prints at most N rows")),
+        SubCommand::with_name("delete")
+            .about("Deletes rows which meet the condition")
+            .arg(Arg::with_name("table").required(true).help("Name of the table"))
+            .arg(Arg::with_name("where")
+                .long("where")
+                .takes_value(true)
+                .required(true)
+                .value_name("condition")
+                .help("SQL condition of rows to delete"))
+            .arg(Arg::with_name("yes")
+                .long("yes")
+                .help("Deletes without asking for confirmation")),
+        SubCommand::with_name("migrate")
+            .about("Applies or reverts numbered NNNN_name.up.sql and NNNN_name.down.sql files")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .arg(Arg::with_name("dir")
+                .long("dir")
+                .takes_value(true)
+                .global(true)
+                .value_name("directory")
+                .help("Directory with the migration files, ./migrations by default"))
+            .subcommand(SubCommand::with_name("status")
+                .about("Lists migrations and whether they are applied"))
+            .subcommand(SubCommand::with_name("up")
+                .about("Applies pending migrations, all of them unless a count is given")
+                .arg(count_arg())
+                .arg(dry_run_arg()))
+            .subcommand(SubCommand::with_name("down")
+                .about("Reverts applied migrations, the last one unless a count is given")
+                .arg(count_arg())
+                .arg(dry_run_arg())),
+        SubCommand::with_name("completions")
+            .about("Prints a completion script for the shell, table and column names are completed from the --path database")
+            .arg(Arg::with_name("shell")
+                .required(true)
+                .possible_values(&["bash", "zsh", "fish"])),
+        SubCommand::with_name("man")
+            .about("Prints the man page, e.g. sqlite man > sqlite.1"),
+    ]
+}
+
+fn count_arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("count")
+        .value_name("N")
+        .validator(validate_number)
+        .help("Count of migrations")
+}
+
+fn validate_number(value: String) -> Result<(), String> {
+    value.parse::<usize>().map(|_| ()).map_err(|_| format!("'{}' is not a number", value))
+}
+
+/// Whether the subcommand changes the database, without --path the changes would be made in memory
+/// and lost when it exits.
+pub fn changes_database(name: &str) -> bool {
+    matches!(name, "create-table" | "insert" | "delete" | "migrate")
+}
+
+/// Runs a subcommand which works on the opened database.
+pub fn run_subcommand(app: &App, name: &str, matches: &clap::ArgMatches) -> Result<()> {
+    let table = matches.value_of("table").unwrap_or_default();
+    let values = |name| matches.values_of(name).map(|values| values.collect::<Vec<_>>()).unwrap_or_default();
+
+    match name {
+        "tables" => commands::tables(app),
+        "schema" => commands::schema(app, table),
+        "create-table" => commands::create_table(app, table, &values("column")),
+        "insert" => commands::insert(app, table, &values("values")),
+        "select" => commands::select(
+            app, table, &values("sort"), matches.value_of("where"),
+            matches.value_of("limit").and_then(|limit| limit.parse().ok()),
+        ),
+        "delete" => commands::delete(app, table, matches.value_of("where").unwrap_or_default(), matches.is_present("yes")),
+        "migrate" => migrations::run_command(app, &migrate_command(matches)),
+        _ => unreachable!("subcommand '{}' is not handled", name),
+    }
+}
+
+fn dry_run_arg<'a, 'b>() -> clap::Arg<'a, 'b> {
+    clap::Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("Prints statements of the migrations without executing them")
+}
+
+fn migrate_command(matches: &clap::ArgMatches) -> migrations::Command {
+    use migrations::Action;
+
+    let (action, sub_matches) = match matches.subcommand() {
+        ("up", Some(sub_matches)) => (Action::Up(sub_matches.value_of("count").and_then(|n| n.parse().ok())), Some(sub_matches)),
+        ("down", Some(sub_matches)) => (Action::Down(sub_matches.value_of("count").and_then(|n| n.parse().ok())), Some(sub_matches)),
+        (_, sub_matches) => (Action::Status, sub_matches),
+    };
+
+    // --dir is global, a value given after the action is only seen by its matches.
+    // It has no default_value, the default would hide a value given before the action.
+    let directory = sub_matches.and_then(|m| m.value_of("dir"))
+        .or_else(|| matches.value_of("dir"))
+        .unwrap_or(migrations::DEFAULT_DIRECTORY);
+
+    migrations::Command {
+        directory: directory.to_string(),
+        action,
+        dry_run: sub_matches.is_some_and(|m| m.is_present("dry-run")),
+    }
+}
+
+/// Runs subcommands which need neither the settings nor the database, false for all others.
+pub fn run_standalone(matches: &clap::ArgMatches) -> Result<bool> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    match matches.subcommand() {
+        ("completions", Some(sub_matches)) => {
+            let shell = sub_matches.value_of("shell").unwrap_or_default();
+            write_completions(shell, &mut out)?;
+        },
+        ("man", _) => write_man_page(&mut out)?,
+        ("complete-names", Some(sub_matches)) => {
+            // Completion has to stay quiet, a missing or unreadable database completes nothing
+            let names = matches.value_of("path")
+                .map(|path| complete_names(path, sub_matches.value_of("kind").unwrap_or_default(), sub_matches.value_of("table")))
+                .unwrap_or_default();
+
+            for name in names {
+                writeln!(out, "{}", name)?;
+            }
+        },
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+fn complete_names(path: &str, kind: &str, table: Option<&str>) -> Vec<String> {
+    let connection = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(connection) => connection,
+        Err(_) => return Vec::new(),
+    };
+
+    let names = match (kind, table) {
+        ("tables", _) => db::table_names(&connection),
+        ("columns", Some(table)) => db::table_columns(&connection, table)
+            .map(|columns| columns.into_iter().map(|column| column.name).collect()),
+        _ => Ok(Vec::new()),
+    };
+
+    names.unwrap_or_default()
+}
+
+/// Script generated by clap followed by functions which complete names from the database.
+pub fn write_completions<W: Write>(shell: &str, out: &mut W) -> Result<()> {
+    let (clap_shell, names) = match shell {
+        "bash" => (clap::Shell::Bash, BASH_NAMES),
+        "zsh" => (clap::Shell::Zsh, ZSH_NAMES),
+        _ => (clap::Shell::Fish, FISH_NAMES),
+    };
+
+    let mut script = Vec::new();
+    build().gen_completions_to(BIN_NAME, clap_shell, &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+
+    if shell == "zsh" {
+        lazy_static! {
+            static ref TABLE_REGEX: Regex = Regex::new(r"(?m)^':table(.*):_files' \\$").unwrap();
+            static ref VALUES_REGEX: Regex = Regex::new(r"(?m)^':values.*:_files' \\$").unwrap();
+            static ref SORT_REGEX: Regex = Regex::new(r"(?m)^('\*--sort=\[[^\]]*\])'").unwrap();
+        }
+
+        // Positional arguments are completed as files by default. The help of values is left out,
+        // clap does not escape its quotes and colons for zsh.
+        script = TABLE_REGEX.replace_all(&script, "':table$1:_sqlite_tables' \\").into_owned();
+        script = VALUES_REGEX.replace_all(&script, "':values:_sqlite_values' \\").into_owned();
+        script = SORT_REGEX.replace_all(&script, "$1: :_sqlite_columns'").into_owned();
+
+        // The script ends by calling the completion, the functions have to be defined before
+        if let Some(position) = script.rfind("_sqlite \"$@\"") {
+            script.insert_str(position, &format!("{}\n", names));
+        }
+        out.write_all(script.as_bytes())?;
+    } else {
+        out.write_all(script.as_bytes())?;
+        writeln!(out, "\n{}", names)?;
+    }
+
+    Ok(())
+}
+
+// Table is the first argument after the subcommand, the database comes from -p or --path.
+// Bash splits words on =, values after it are left to the default completion.
+const BASH_NAMES: &str = r#"_sqlite_names() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local path="" command="" table_index=0 i
+
+    for (( i=1; i < COMP_CWORD; i++ )); do
+        case "${COMP_WORDS[i]}" in
+            -p|--path) path="${COMP_WORDS[i+1]}"; (( i++ )) ;;
+            schema|insert|select|delete) [[ -z "$command" ]] && command="${COMP_WORDS[i]}" && table_index=$(( i + 1 )) ;;
+        esac
+    done
+
+    if [[ -z "$path" || -z "$command" || "$cur" == -* ]]; then
+        _sqlite
+        return
+    fi
+
+    local table="${COMP_WORDS[table_index]}"
+    if (( COMP_CWORD == table_index )); then
+        COMPREPLY=( $(compgen -W "$(sqlite --path "$path" complete-names tables 2>/dev/null)" -- "$cur") )
+    elif [[ "$command" == select && "$prev" == --sort ]]; then
+        COMPREPLY=( $(compgen -W "$(sqlite --path "$path" complete-names columns "$table" 2>/dev/null)" -- "$cur") )
+    elif [[ "$command" == insert && "$cur" != = && "$prev" != = ]]; then
+        compopt -o nospace
+        COMPREPLY=( $(compgen -S = -W "$(sqlite --path "$path" complete-names columns "$table" 2>/dev/null)" -- "$cur") )
+    else
+        _sqlite
+    fi
+}
+
+complete -F _sqlite_names -o bashdefault -o default sqlite"#;
+
+const ZSH_NAMES: &str = r#"_sqlite_database() {
+    local -a tokens; tokens=(${(z)BUFFER})
+    local i
+    for (( i = 1; i < ${#tokens}; i++ )); do
+        case $tokens[i] in
+            (-p|--path) print -r -- ${(Q)tokens[i+1]}; return ;;
+        esac
+    done
+}
+
+_sqlite_tables() {
+    local database=$(_sqlite_database)
+    [[ -n $database ]] || return 1
+    local -a tables; tables=(${(f)"$(sqlite --path $database complete-names tables 2>/dev/null)"})
+    _describe -t tables 'table' tables
+}
+
+_sqlite_columns() {
+    local database=$(_sqlite_database)
+    [[ -n $database ]] || return 1
+    local -a columns; columns=(${(f)"$(sqlite --path $database complete-names columns $line[1] 2>/dev/null)"})
+    compadd "$@" -- $columns
+}
+
+_sqlite_values() {
+    _sqlite_columns -S =
+}
+"#;
+
+const FISH_NAMES: &str = r#"function __sqlite_database
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] -p --path
+            echo $tokens[(math $i + 1)]
+            return
+        end
+    end
+end
+
+function __sqlite_table
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] schema insert select delete
+            echo $tokens[(math $i + 1)]
+            return
+        end
+    end
+end
+
+function __sqlite_needs_table
+    set -l tokens (commandline -opc)
+    contains -- $tokens[-1] schema insert select delete
+end
+
+function __sqlite_names
+    set -l database (__sqlite_database)
+    test -n "$database"; and sqlite --path $database complete-names $argv 2>/dev/null
+end
+
+complete -c sqlite -n "__sqlite_needs_table" -f -a "(__sqlite_names tables)"
+complete -c sqlite -n "__fish_seen_subcommand_from select" -l sort -x -a "(__sqlite_names columns (__sqlite_table))"
+complete -c sqlite -n "__fish_seen_subcommand_from insert; and not __sqlite_needs_table" -f -a "(__sqlite_names columns (__sqlite_table))="
+"#;
+
+/// Roff page put together from the help of every subcommand.
+pub fn write_man_page<W: Write>(out: &mut W) -> Result<()> {
+    writeln!(out, ".TH SQLITE 1 \"\" \"{} {}\"", BIN_NAME, VERSION)?;
+    writeln!(out, ".SH NAME\n{} \\- interactive demonstration of sqlite basic features", BIN_NAME)?;
+    writeln!(out, ".SH SYNOPSIS\n.B {}\n[OPTIONS] [SUBCOMMAND]", BIN_NAME)?;
+    writeln!(out, ".SH DESCRIPTION")?;
+    writeln!(out, "Without a subcommand menus guide through defining tables, inserting, browsing and comparing rows \
+        and maintaining the database. Subcommands run a single action for scripts and exit.")?;
+
+    writeln!(out, ".SH OPTIONS")?;
+    write_help(out, &mut build().set_term_width(MAN_WIDTH).template("{unified}"))?;
+
+    writeln!(out, ".SH SUBCOMMANDS")?;
+    for subcommand in subcommands() {
+        writeln!(out, ".SS {} {}", BIN_NAME, subcommand.get_name())?;
+        let mut subcommand = subcommand
+            .setting(clap::AppSettings::DisableVersion)
+            .set_term_width(MAN_WIDTH)
+            .template("{about}\n\n{all-args}");
+        write_help(out, &mut subcommand)?;
+    }
+
+    writeln!(out, ".SH FILES")?;
+    writeln!(out, ".TP\n$XDG_CONFIG_HOME/sqlite-cli-demo/config.toml\nSettings, changed from the Settings menu")?;
+    writeln!(out, ".TP\n$XDG_DATA_HOME/sqlite-cli-demo/history.db\nHistory of executed queries")?;
+
+    Ok(())
+}
+
+fn write_help<W: Write>(out: &mut W, app: &mut clap::App) -> Result<()> {
+    let mut help = Vec::new();
+    app.write_long_help(&mut help).map_err(|err| crate::error::Error::validation(err.to_string()))?;
+
+    writeln!(out, ".nf")?;
+    for line in String::from_utf8_lossy(&help).lines() {
+        // Lines starting with a dot or a quote would be read as requests
+        let line = line.replace('\\', "\\e");
+        if line.starts_with('.') || line.starts_with('\'') {
+            write!(out, "\\&")?;
+        }
+        writeln!(out, "{}", line)?;
+    }
+    writeln!(out, ".fi")?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+// Menu actions as command line subcommands, so they can be scripted. Input is checked by the same
+// validators as the prompts and results are printed in the configured output format.
+
+use rusqlite::{params, types::Value};
+
+use crate::app::App;
+use crate::db::{self, Column, ColumnDefinition, TableDefinition};
+use crate::display::{draw_query, draw_result};
+use crate::error::{Error, Result};
+use crate::impact::confirm_with_preview;
+use crate::utils::{validate_column_name, validate_sql_type, validate_table_name, did_you_mean};
+use crate::value_parser::{Affinity, parse_value};
+
+pub fn tables(app: &App) -> Result<()> {
+    draw_query(app, "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name", &[])
+}
+
+pub fn schema(app: &App, table: &str) -> Result<()> {
+    // Fails with a readable message for missing tables
+    db::table_columns(app.require_connection()?, table)?;
+
+    draw_query(
+        app,
+        "SELECT name, type, \"notnull\" AS not_null, dflt_value AS \"default\", pk AS primary_key FROM pragma_table_info(?) ORDER BY cid",
+        params![table],
+    )
+}
+
+/// `columns` are given as `name:type` or `name:type=default`.
+pub fn create_table(app: &App, table: &str, columns: &[&str]) -> Result<()> {
+    app.require_writable()?;
+    validate_table_name(table).map_err(Error::Validation)?;
+
+    let connection = app.require_connection()?;
+    if db::table_exists(connection, table)? {
+        return Err(Error::validation(format!("Table '{}' already exists", table)));
+    }
+
+    let mut definition = TableDefinition::new();
+    definition.set_name(table);
+    for column in columns {
+        definition.add_column(parse_column_definition(column)?);
+    }
+
+    definition.create_query()?.execute(connection)?;
+    println!("Created table '{}'", table);
+    Ok(())
+}
+
+fn parse_column_definition(text: &str) -> Result<ColumnDefinition> {
+    let (name, rest) = text.split_once(':')
+        .ok_or_else(|| Error::validation(format!("Column '{}' has to be given as name:type", text)))?;
+    let (sql_type, default) = match rest.split_once('=') {
+        Some((sql_type, default)) => (sql_type.trim(), Some(default)),
+        None => (rest.trim(), None),
+    };
+
+    validate_column_name(name).map_err(Error::Validation)?;
+    if !validate_sql_type(sql_type) {
+        return Err(Error::validation(format!("Type '{}' of column '{}' must be alphanumeric", sql_type, name)));
+    }
+
+    let default = match default {
+        Some(default) if !default.trim().is_empty() => {
+            Some(parse_value(default, Affinity::from_sql_type(sql_type)).map_err(Error::Validation)?)
+        },
+        _ => None,
+    };
+
+    Ok(ColumnDefinition { name: name.to_string(), sql_type: sql_type.to_string(), default, json: false })
+}
+
+/// `assignments` are given as `column=value`, values are read like in the insert prompt.
+/// Columns which are not given get their default.
+pub fn insert(app: &App, table: &str, assignments: &[&str]) -> Result<()> {
+    app.require_writable()?;
+
+    let connection = app.require_connection()?;
+    let columns = db::table_columns(connection, table)?;
+
+    let mut values: Vec<(String, Value)> = Vec::with_capacity(assignments.len());
+    for assignment in assignments {
+        let (name, text) = assignment.split_once('=')
+            .ok_or_else(|| Error::validation(format!("Value '{}' has to be given as column=value", assignment)))?;
+        let column = find_column(&columns, table, name)?;
+
+        if values.iter().any(|(name, _)| *name == column.name) {
+            return Err(Error::validation(format!("Column '{}' is given more than once", column.name)));
+        }
+
+        let value = parse_value(text, Affinity::from_sql_type(&column.sqltype))
+            .map_err(|cause| Error::validation(format!("Column '{}': {}", column.name, cause)))?;
+        values.push((column.name.clone(), value));
+    }
+
+    if values.is_empty() {
+        return Err(Error::validation("Give at least one column=value"));
+    }
+
+    db::insert_columns(table, values).execute(connection)?;
+    println!("Inserted row");
+    Ok(())
+}
+
+/// `sorting` is given as `column`, `column:asc` or `column:desc`. `condition` is raw SQL placed after WHERE.
+pub fn select(app: &App, table: &str, sorting: &[&str], condition: Option<&str>, limit: Option<usize>) -> Result<()> {
+    let connection = app.require_connection()?;
+    let columns = db::table_columns(connection, table)?;
+
+    let mut order: Vec<(&str, bool)> = Vec::with_capacity(sorting.len());
+    for term in sorting {
+        let (name, direction) = term.split_once(':').unwrap_or((term, "asc"));
+        let ascending = match direction.to_lowercase().as_str() {
+            "asc" => true,
+            "desc" => false,
+            _ => return Err(Error::validation(format!("Sort order of '{}' has to be asc or desc", name))),
+        };
+
+        order.push((find_column(&columns, table, name)?.name.as_str(), ascending));
+    }
+
+    let query = db::select(table, condition, &order, limit);
+    let result = db::fetch(connection, &query.sql, &[])?;
+    draw_result(&app.config, &result)
+}
+
+/// Without `yes` the affected rows are previewed and the statement is confirmed as configured in the settings.
+pub fn delete(app: &App, table: &str, condition: &str, yes: bool) -> Result<()> {
+    app.require_writable()?;
+
+    let connection = app.require_connection()?;
+    db::table_columns(connection, table)?;
+
+    let query = db::delete(table, Some(condition));
+    if !yes && !confirm_with_preview(app, &db::quote_identifier(table), Some(condition), None, &query.sql)? {
+        return Ok(());
+    }
+
+    let count = query.execute(connection)?;
+    println!("Deleted {} rows", count);
+    Ok(())
+}
+
+// Column names are case insensitive in sqlite
+fn find_column<'a>(columns: &'a [Column], table: &str, name: &str) -> Result<&'a Column> {
+    columns.iter()
+        .find(|column| column.name == name)
+        .or_else(|| columns.iter().find(|column| column.name.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| Error::validation(format!(
+            "Table '{}' has no column '{}'.{}", table, name, did_you_mean(name, columns.iter().map(|column| column.name.as_str()))
+        )))
+}
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use console::{style, Style};
+use serde::{Serialize, Deserialize};
+
+use crate::app::App;
+use crate::error::{Error, Result};
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, wait_for_keypress, ValidatorAdaptor};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmationPolicy {
+    Always,
+    Destructive,
+    Never,
+}
+
+impl ConfirmationPolicy {
+    const ALL: [ConfirmationPolicy; 3] = [ConfirmationPolicy::Always, ConfirmationPolicy::Destructive, ConfirmationPolicy::Never];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfirmationPolicy::Always => "always",
+            ConfirmationPolicy::Destructive => "destructive",
+            ConfirmationPolicy::Never => "never",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Plain,
+    Csv,
+}
+
+impl OutputFormat {
+    const ALL: [OutputFormat; 3] = [OutputFormat::Table, OutputFormat::Plain, OutputFormat::Csv];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Plain => "plain",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+// Styles are written as console dotted strings, e.g. "yellow.bold"
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub values: String,
+    pub indicator: String,
+    pub yes: String,
+    pub no: String,
+    // Highlighting of SQL in previews
+    pub keyword: String,
+    pub identifier: String,
+    pub literal: String,
+    pub comment: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            values: String::from("yellow.dim"),
+            indicator: String::from("yellow.bold"),
+            yes: String::from("yellow.dim"),
+            no: String::from("yellow.dim"),
+            keyword: String::from("cyan.bold"),
+            identifier: String::from("green"),
+            literal: String::from("yellow"),
+            comment: String::from("dim"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub page_size: usize,
+    pub truncate_width: usize,
+    pub null_display: String,
+    pub confirmation: ConfirmationPolicy,
+    // Deletes and updates touching more rows have to be confirmed by typing the row count
+    pub typed_confirmation_rows: usize,
+    pub output_format: OutputFormat,
+    pub theme: ThemeConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            page_size: 50,
+            truncate_width: 20,
+            null_display: String::from("NULL"),
+            confirmation: ConfirmationPolicy::Always,
+            typed_confirmation_rows: 100,
+            output_format: OutputFormat::Table,
+            theme: ThemeConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // Missing file is not an error, defaults are used until settings are saved for the first time.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|err| Error::validation(format!("Invalid config. Error: {}", err))),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self)
+            .map_err(|err| Error::validation(format!("Could not serialize config. Error: {}", err)))?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+pub fn default_config_path() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    config_home.join("sqlite-cli-demo").join("config.toml")
+}
+
+pub fn validate_style(input: &str) -> bool {
+    // Words understood by console::Style::from_dotted_str, anything else would be silently ignored
+    const STYLE_WORDS: [&str; 22] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        "on_black", "on_red", "on_green", "on_yellow", "on_blue", "on_magenta", "on_cyan", "on_white",
+        "bold", "dim", "underlined", "blink", "reverse", "hidden",
+    ];
+
+    !input.is_empty() && input.split('.').all(|word| STYLE_WORDS.contains(&word))
+}
+
+#[derive(Clone, Copy)]
+enum SettingsOption {
+    PageSize,
+    TruncateWidth,
+    NullDisplay,
+    Confirmation,
+    TypedConfirmation,
+    OutputFormat,
+    ValuesStyle,
+    IndicatorStyle,
+    YesStyle,
+    NoStyle,
+    KeywordStyle,
+    IdentifierStyle,
+    LiteralStyle,
+    CommentStyle,
+    Save,
+    Back,
+}
+
+pub fn settings(app: &mut App) -> Result<()> {
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+        println!("Settings are stored in {}\n", style(app.config_path.display()).cyan());
+
+        use SettingsOption::*;
+        let config = &app.config;
+        let options: Vec<(SettingsOption, String)> = vec![
+            (PageSize, format!("Page size: {}", config.page_size)),
+            (TruncateWidth, format!("Truncation width: {}", config.truncate_width)),
+            (NullDisplay, format!("NULL displayed as: {}", config.null_display)),
+            (Confirmation, format!("Confirm queries: {}", config.confirmation.name())),
+            (TypedConfirmation, format!("Type row count when changing more than: {} rows", config.typed_confirmation_rows)),
+            (OutputFormat, format!("Output format: {}", config.output_format.name())),
+            (ValuesStyle, format!("Values style: {}", Style::from_dotted_str(&config.theme.values).apply_to(&config.theme.values))),
+            (IndicatorStyle, format!("Indicator style: {}", Style::from_dotted_str(&config.theme.indicator).apply_to(&config.theme.indicator))),
+            (YesStyle, format!("Yes style: {}", Style::from_dotted_str(&config.theme.yes).apply_to(&config.theme.yes))),
+            (NoStyle, format!("No style: {}", Style::from_dotted_str(&config.theme.no).apply_to(&config.theme.no))),
+            (KeywordStyle, format!("SQL keyword style: {}", Style::from_dotted_str(&config.theme.keyword).apply_to(&config.theme.keyword))),
+            (IdentifierStyle, format!("SQL identifier style: {}", Style::from_dotted_str(&config.theme.identifier).apply_to(&config.theme.identifier))),
+            (LiteralStyle, format!("SQL literal style: {}", Style::from_dotted_str(&config.theme.literal).apply_to(&config.theme.literal))),
+            (CommentStyle, format!("SQL comment style: {}", Style::from_dotted_str(&config.theme.comment).apply_to(&config.theme.comment))),
+            (Save, String::from("Save")),
+            (Back, String::from("Back")),
+        ];
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (_, text) in &options {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        match options[last_chosen].0 {
+            PageSize => { app.config.page_size = ask_for_size(app, "Rows per page", app.config.page_size)?; },
+            TruncateWidth => { app.config.truncate_width = ask_for_size(app, "Characters shown per cell", app.config.truncate_width)?; },
+            NullDisplay => {
+                app.config.null_display = Input::with_theme(&app.view.dialog_theme)
+                    .with_prompt("Show NULL as")
+                    .default(app.config.null_display.clone())
+                    .interact()?;
+            },
+            Confirmation => {
+                let policies = ConfirmationPolicy::ALL;
+                let names: Vec<&str> = policies.iter().map(|p| p.name()).collect();
+                let chosen = Select::with_theme(&app.view.dialog_theme)
+                    .items(&names)
+                    .default(policies.iter().position(|p| *p == app.config.confirmation).unwrap_or(0))
+                    .interact()?;
+                app.config.confirmation = policies[chosen];
+            },
+            TypedConfirmation => {
+                app.config.typed_confirmation_rows = ask_for_size(app, "Rows changed before the count has to be typed", app.config.typed_confirmation_rows)?;
+            },
+            OutputFormat => {
+                let formats = crate::config::OutputFormat::ALL;
+                let names: Vec<&str> = formats.iter().map(|f| f.name()).collect();
+                let chosen = Select::with_theme(&app.view.dialog_theme)
+                    .items(&names)
+                    .default(formats.iter().position(|f| *f == app.config.output_format).unwrap_or(0))
+                    .interact()?;
+                app.config.output_format = formats[chosen];
+            },
+            ValuesStyle => { app.config.theme.values = ask_for_style(app, "Values style", &app.config.theme.values)?; },
+            IndicatorStyle => { app.config.theme.indicator = ask_for_style(app, "Indicator style", &app.config.theme.indicator)?; },
+            YesStyle => { app.config.theme.yes = ask_for_style(app, "Yes style", &app.config.theme.yes)?; },
+            NoStyle => { app.config.theme.no = ask_for_style(app, "No style", &app.config.theme.no)?; },
+            KeywordStyle => { app.config.theme.keyword = ask_for_style(app, "SQL keyword style", &app.config.theme.keyword)?; },
+            IdentifierStyle => { app.config.theme.identifier = ask_for_style(app, "SQL identifier style", &app.config.theme.identifier)?; },
+            LiteralStyle => { app.config.theme.literal = ask_for_style(app, "SQL literal style", &app.config.theme.literal)?; },
+            CommentStyle => { app.config.theme.comment = ask_for_style(app, "SQL comment style", &app.config.theme.comment)?; },
+            Save => {
+                app.config.save(&app.config_path)?;
+                println!("Settings were saved\n");
+                wait_for_keypress();
+            },
+            Back => { break; },
+        }
+
+        app.apply_config();
+    }
+
+    Ok(())
+}
+
+fn ask_for_size(app: &App, prompt: &str, current: usize) -> Result<usize> {
+    Input::with_theme(&app.view.dialog_theme)
+        .with_prompt(prompt)
+        .default(current)
+        .validate_with(ValidatorAdaptor::new(|text| matches!(text.parse::<usize>(), Ok(size) if size > 0), String::from("Must be a positive number")))
+        .interact()
+}
+
+fn ask_for_style(app: &App, prompt: &str, current: &str) -> Result<String> {
+    Input::with_theme(&app.view.dialog_theme)
+        .with_prompt(format!("{} (e.g. yellow.bold)", prompt).as_str())
+        .default(current.to_string())
+        .validate_with(ValidatorAdaptor::new(validate_style, String::from("Use dot separated colors and attributes, e.g. cyan.on_black.bold")))
+        .interact()
+}
@@ -0,0 +1,346 @@
+// Compares rows of two tables matched by key columns. The right table may be in the same database or
+// in another file, which is attached to the connection for the time of the comparison.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use console::style;
+use rusqlite::{Connection, types::Value};
+
+use crate::app::App;
+use crate::db::{self, Column, Query, ResultColumn, ResultSet, quote_identifier, to_sqlite_literal};
+use crate::display::draw_result;
+use crate::error::{Error, Result};
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, wait_for_keypress, ValidatorAdaptor};
+
+const ATTACHED_SCHEMA: &str = "diff_other";
+
+/// Table in a schema of the connection, "main" for the open database.
+pub struct TableRef {
+    pub schema: String,
+    pub table: String,
+}
+
+impl TableRef {
+    pub fn new(schema: &str, table: &str) -> Self {
+        Self { schema: schema.to_string(), table: table.to_string() }
+    }
+
+    fn qualified(&self) -> String {
+        format!("{}.{}", quote_identifier(&self.schema), quote_identifier(&self.table))
+    }
+}
+
+pub struct ChangedRow {
+    pub key: Vec<Value>,
+    // Column with the value on the left and on the right
+    pub changes: Vec<(String, Value, Value)>,
+}
+
+pub struct DataDiff {
+    pub keys: Vec<String>,
+    // Both tables have them, key columns first
+    pub columns: Vec<String>,
+    pub only_left: Vec<Vec<Value>>,
+    pub only_right: Vec<Vec<Value>>,
+    pub changed: Vec<ChangedRow>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl DataDiff {
+    /// Rows are matched with IS, so NULL keys match each other. Keys are expected to be unique,
+    /// duplicates show up as several changed rows.
+    pub fn compare(connection: &Connection, left: &TableRef, right: &TableRef, keys: &[String]) -> Result<Self> {
+        let left_columns = column_names(connection, left)?;
+        let right_columns = column_names(connection, right)?;
+        let compared: Vec<String> = left_columns.iter()
+            .filter(|column| right_columns.contains(column) && !keys.contains(column))
+            .cloned()
+            .collect();
+
+        for key in keys {
+            if !right_columns.contains(key) || !left_columns.contains(key) {
+                return Err(Error::validation(format!("Key column '{}' is not in both tables", key)));
+            }
+        }
+
+        let columns: Vec<String> = keys.iter().chain(compared.iter()).cloned().collect();
+        let matching = |a: &str, b: &str| -> String {
+            keys.iter()
+                .map(|key| format!("{a}.{k} IS {b}.{k}", a = a, b = b, k = quote_identifier(key)))
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        };
+        let select = |alias: &str, names: &[String]| -> String {
+            names.iter().map(|column| format!("{}.{}", alias, quote_identifier(column))).collect::<Vec<String>>().join(", ")
+        };
+
+        let only_in = |this: &TableRef, that: &TableRef| -> Result<Vec<Vec<Value>>> {
+            let sql = format!(
+                "SELECT {} FROM {} AS a WHERE NOT EXISTS (SELECT 1 FROM {} AS b WHERE {})",
+                select("a", &columns), this.qualified(), that.qualified(), matching("a", "b")
+            );
+            Ok(db::fetch(connection, &sql, &[])?.rows)
+        };
+
+        let only_left = only_in(left, right)?;
+        let only_right = only_in(right, left)?;
+
+        let mut changed = Vec::new();
+        if !compared.is_empty() {
+            let differs: Vec<String> = compared.iter()
+                .map(|column| format!("l.{c} IS NOT r.{c}", c = quote_identifier(column)))
+                .collect();
+            let sql = format!(
+                "SELECT {}, {}, {} FROM {} AS l JOIN {} AS r ON {} WHERE {}",
+                select("l", keys), select("l", &compared), select("r", &compared),
+                left.qualified(), right.qualified(), matching("l", "r"), differs.join(" OR ")
+            );
+
+            for row in db::fetch(connection, &sql, &[])?.rows {
+                let (key, values) = row.split_at(keys.len());
+                let (left_values, right_values) = values.split_at(compared.len());
+
+                let changes = compared.iter().zip(left_values.iter().zip(right_values.iter()))
+                    .filter(|(_, (l, r))| l != r)
+                    .map(|(column, (l, r))| (column.clone(), l.clone(), r.clone()))
+                    .collect();
+
+                changed.push(ChangedRow { key: key.to_vec(), changes });
+            }
+        }
+
+        Ok(DataDiff { keys: keys.to_vec(), columns, only_left, only_right, changed })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.changed.is_empty()
+    }
+
+    /// Rows of only one side as a result set, for the table renderer.
+    pub fn rows(&self, rows: &[Vec<Value>]) -> ResultSet {
+        ResultSet { columns: self.result_columns(&self.columns), rows: rows.to_vec() }
+    }
+
+    /// One row per changed value, with the key, column name, left and right value.
+    pub fn changes(&self) -> ResultSet {
+        let mut names = self.keys.clone();
+        names.extend(vec![String::from("column"), String::from("left"), String::from("right")]);
+
+        let rows = self.changed.iter()
+            .flat_map(|row| row.changes.iter().map(move |(column, left, right)| {
+                let mut values = row.key.clone();
+                values.extend(vec![Value::Text(column.clone()), left.clone(), right.clone()]);
+                values
+            }))
+            .collect();
+
+        ResultSet { columns: self.result_columns(&names), rows }
+    }
+
+    fn result_columns(&self, names: &[String]) -> Vec<ResultColumn> {
+        names.iter().map(|name| ResultColumn { name: name.clone(), decl_type: None }).collect()
+    }
+
+    /// Statements which make the `target` table the same as the other side. They use the bare table
+    /// name, so they are meant to run in the database of the target.
+    pub fn sync_queries(&self, direction: SyncDirection, target: &str) -> Vec<Query> {
+        let (missing, extra) = match direction {
+            SyncDirection::LeftToRight => (&self.only_left, &self.only_right),
+            SyncDirection::RightToLeft => (&self.only_right, &self.only_left),
+        };
+
+        let mut queries = Vec::new();
+
+        for row in extra {
+            queries.push(db::delete(target, Some(&self.key_condition(&row[..self.keys.len()]))));
+        }
+
+        for row in &self.changed {
+            let assignments = row.changes.iter()
+                .map(|(column, left, right)| match direction {
+                    SyncDirection::LeftToRight => (column.clone(), left.clone()),
+                    SyncDirection::RightToLeft => (column.clone(), right.clone()),
+                })
+                .collect();
+            queries.push(db::update(target, assignments, Some(&self.key_condition(&row.key))));
+        }
+
+        for row in missing {
+            queries.push(db::insert_columns(target, self.columns.iter().cloned().zip(row.iter().cloned()).collect()));
+        }
+
+        queries
+    }
+
+    // Literals instead of parameters, the condition is raw SQL for db::update and db::delete
+    fn key_condition(&self, key: &[Value]) -> String {
+        self.keys.iter().zip(key.iter())
+            .map(|(column, value)| format!("{} IS {}", quote_identifier(column), to_sqlite_literal(value)))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+}
+
+fn column_names(connection: &Connection, table: &TableRef) -> Result<Vec<String>> {
+    Ok(db::table_columns_in(connection, &table.schema, &table.table)?.into_iter().map(|column| column.name).collect())
+}
+
+pub fn data_diff(app: &App) -> Result<()> {
+    clear()?;
+    let left_table = app.require_active_table()?.to_string();
+    let connection = app.require_connection()?;
+
+    println!("Compare rows of '{}' with another table\n", style(&left_table).cyan());
+
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Database file of the other table (empty for this one)")
+        .allow_empty(true)
+        .interact()?;
+
+    let schema = if path.trim().is_empty() {
+        "main"
+    } else {
+        if !std::path::Path::new(&path).exists() {
+            return Err(Error::validation(format!("Database file '{}' does not exist", path)));
+        }
+        connection.execute(&format!("ATTACH DATABASE ? AS {}", ATTACHED_SCHEMA), &[&path])?;
+        ATTACHED_SCHEMA
+    };
+
+    let result = compare_with(app, connection, &left_table, schema, &path);
+
+    if schema == ATTACHED_SCHEMA {
+        connection.execute_batch(&format!("DETACH DATABASE {}", ATTACHED_SCHEMA))?;
+    }
+
+    result
+}
+
+fn compare_with(app: &App, connection: &Connection, left_table: &str, schema: &str, path: &str) -> Result<()> {
+    let right_table: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Other table")
+        .default(left_table.to_string())
+        .interact()?;
+
+    let left = TableRef::new("main", left_table);
+    let right = TableRef::new(schema, &right_table);
+    if db::table_columns_in(connection, schema, &right_table).is_err() {
+        return Err(Error::validation(format!("Table '{}' does not exist", right_table)));
+    }
+
+    let keys = ask_for_keys(app, &db::table_columns(connection, left_table)?)?;
+    let diff = DataDiff::compare(connection, &left, &right, &keys)?;
+
+    println!();
+    if diff.is_empty() {
+        println!("Tables have the same rows\n");
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    // Tables usually have the same name in both files
+    let left_name = format!("'{}'", left_table);
+    let right_name = match schema {
+        "main" => format!("'{}'", right_table),
+        _ => format!("'{}' in '{}'", right_table, path),
+    };
+
+    print_section(app, &format!("Only in {}", left_name), diff.only_left.len(), diff.rows(&diff.only_left))?;
+    print_section(app, &format!("Only in {}", right_name), diff.only_right.len(), diff.rows(&diff.only_right))?;
+    print_section(app, "Changed", diff.changed.len(), diff.changes())?;
+
+    let option = Select::with_theme(&app.view.dialog_theme)
+        .with_prompt("Export sync statements")
+        .default(0)
+        .item(format!("Make {} like {}", right_name, left_name).as_str())
+        .item(format!("Make {} like {}", left_name, right_name).as_str())
+        .item("Back")
+        .interact()?;
+
+    let (direction, target) = match option {
+        0 => (SyncDirection::LeftToRight, right_table.as_str()),
+        1 => (SyncDirection::RightToLeft, left_table),
+        _ => return Ok(()),
+    };
+
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Output file")
+        .default(String::from("sync.sql"))
+        .interact()?;
+
+    let mut out = BufWriter::new(File::create(&path)?);
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    for query in diff.sync_queries(direction, target) {
+        writeln!(out, "{};", query.preview())?;
+    }
+    writeln!(out, "COMMIT;")?;
+    out.flush()?;
+
+    println!("Sync statements were written into '{}'\n", style(&path).green());
+    wait_for_keypress();
+    Ok(())
+}
+
+// Long sections are cut to a page, the export has every row anyway
+fn print_section(app: &App, title: &str, row_count: usize, mut result: ResultSet) -> Result<()> {
+    println!("{} ({} rows)", style(title).bold(), row_count);
+
+    if row_count == 0 {
+        println!();
+        return Ok(());
+    }
+
+    let lines = result.rows.len();
+    result.rows.truncate(app.config.page_size);
+    draw_result(&app.config, &result)?;
+
+    if lines > app.config.page_size {
+        println!("... and {} more", lines - app.config.page_size);
+    }
+    println!();
+
+    Ok(())
+}
+
+// Primary key when the table has one, otherwise the user names the columns
+fn ask_for_keys(app: &App, columns: &[Column]) -> Result<Vec<String>> {
+    let primary_key: Vec<String> = columns.iter().filter(|c| c.primary_key).map(|c| c.name.clone()).collect();
+
+    if !primary_key.is_empty() {
+        let option = Select::with_theme(&app.view.dialog_theme)
+            .with_prompt("Match rows by")
+            .default(0)
+            .item(format!("Primary key ({})", primary_key.join(", ")).as_str())
+            .item("Chosen key columns")
+            .interact()?;
+
+        if option == 0 {
+            return Ok(primary_key);
+        }
+    }
+
+    let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+    let known = names.clone();
+    let keys: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt(format!("Key columns, separated with commas ({})", names.join(", ")).as_str())
+        .validate_with(ValidatorAdaptor::with_reason(move |text| {
+            match split_columns(text).into_iter().find(|column| !known.contains(column)) {
+                Some(column) => Err(format!("Column '{}' is not in the table", column)),
+                None => Ok(()),
+            }
+        }))
+        .interact()?;
+
+    Ok(split_columns(&keys))
+}
+
+fn split_columns(text: &str) -> Vec<String> {
+    text.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from).collect()
+}
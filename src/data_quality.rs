@@ -0,0 +1,250 @@
+use console::style;
+use prettytable::{Table, Row, Cell, format};
+
+use rusqlite::{Connection, NO_PARAMS, types::Value};
+
+use crate::app::App;
+use crate::config::Config;
+use crate::display::value_repr;
+use crate::error::{Error, Result};
+use crate::prompt::Confirmation;
+use crate::db::{Column, quote_identifier};
+use crate::insert_row::get_table;
+use crate::utils::{clear, wait_for_keypress};
+use crate::value_parser::Affinity;
+
+const STORAGE_CLASSES: [&str; 5] = ["integer", "real", "text", "blob", "null"];
+
+struct ColumnReport {
+    column: String,
+    sql_type: String,
+    affinity: Affinity,
+    // counts in STORAGE_CLASSES order
+    storage_counts: [i64; 5],
+    distinct: i64,
+    min: Value,
+    max: Value,
+    most_common: Vec<(Value, i64)>,
+}
+
+impl ColumnReport {
+    fn mismatched(&self) -> i64 {
+        count_mismatched(self.affinity, &self.storage_counts)
+    }
+}
+
+fn count_mismatched(affinity: Affinity, storage_counts: &[i64; 5]) -> i64 {
+    STORAGE_CLASSES.iter().zip(storage_counts.iter())
+        .filter(|(class, _)| !is_expected(affinity, class))
+        .map(|(_, count)| count)
+        .sum()
+}
+
+pub fn data_quality(app: &mut App) -> Result<()> {
+    clear()?;
+
+    let name = app.require_active_table()?.to_string();
+    let columns = get_table(app)?;
+    let connection = app.require_connection()?;
+
+    let reports: Vec<ColumnReport> = columns.iter()
+        .map(|c| analyze_column(connection, &name, c))
+        .collect::<rusqlite::Result<_>>()?;
+
+    println!("Data quality of \'{}\'\n", style(&name).cyan());
+    print_reports(&app.config, &reports);
+    println!();
+
+    let mismatched: Vec<&ColumnReport> = reports.iter().filter(|r| r.mismatched() > 0).collect();
+    if mismatched.is_empty() {
+        println!("Every value is stored as its column affinity expects.\n");
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    for report in &mismatched {
+        println!(
+            "Column {} has {} values not stored as {}",
+            style(&report.column).cyan(), style(report.mismatched()).red(), report.affinity.name()
+        );
+    }
+    println!();
+
+    if app.is_read_only() {
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    let convert = Confirmation::with_theme(&app.view.dialog_theme)
+        .with_text("Convert mismatched values in place?")
+        .default(false)
+        .interact()?;
+
+    if !convert {
+        return Ok(());
+    }
+
+    let connection = app.require_connection_mut()?;
+    let (converted, skipped) = convert_mismatched(connection, &name, &mismatched)?;
+    println!("Converted {} values, {} could not be converted and were left unchanged\n", converted, skipped);
+
+    wait_for_keypress();
+    Ok(())
+}
+
+fn is_expected(affinity: Affinity, storage_class: &str) -> bool {
+    matches!(
+        (affinity, storage_class),
+        (_, "null")
+            | (Affinity::Blob, _)
+            | (Affinity::Integer, "integer")
+            | (Affinity::Real, "real")
+            | (Affinity::Numeric, "integer")
+            | (Affinity::Numeric, "real")
+            | (Affinity::Text, "text")
+    )
+}
+
+// Values of the column counted in STORAGE_CLASSES order
+fn count_storage_classes(connection: &Connection, table: &str, column: &str) -> rusqlite::Result<[i64; 5]> {
+    let mut storage_counts = [0; 5];
+    let (name, table) = (quote_identifier(column), quote_identifier(table));
+
+    let mut statement = connection.prepare(
+        format!("SELECT typeof({0}), COUNT(*) FROM {1} GROUP BY typeof({0})", name, table).as_str()
+    )?;
+    let mut rows = statement.query(NO_PARAMS)?;
+    while let Some(row) = rows.next()? {
+        let class: String = row.get(0)?;
+        if let Some(i) = STORAGE_CLASSES.iter().position(|c| *c == class) {
+            storage_counts[i] = row.get(1)?;
+        }
+    }
+
+    Ok(storage_counts)
+}
+
+fn analyze_column(connection: &Connection, table: &str, column: &Column) -> rusqlite::Result<ColumnReport> {
+    let storage_counts = count_storage_classes(connection, table, &column.name)?;
+    let (name, table) = (quote_identifier(&column.name), quote_identifier(table));
+
+    let (distinct, min, max) = connection.query_row(
+        format!("SELECT COUNT(DISTINCT {0}), MIN({0}), MAX({0}) FROM {1}", name, table).as_str(),
+        NO_PARAMS,
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut statement = connection.prepare(
+        format!(
+            "SELECT {0}, COUNT(*) AS occurrences FROM {1} WHERE {0} IS NOT NULL GROUP BY {0} ORDER BY occurrences DESC LIMIT 3",
+            name, table
+        ).as_str()
+    )?;
+    let most_common = statement
+        .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(Value, i64)>>>()?;
+
+    Ok(ColumnReport {
+        column: column.name.clone(),
+        sql_type: column.sqltype.clone(),
+        affinity: Affinity::from_sql_type(&column.sqltype),
+        storage_counts,
+        distinct,
+        min,
+        max,
+        most_common,
+    })
+}
+
+fn print_reports(config: &Config, reports: &[ColumnReport]) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+
+    let mut titles = vec!["column", "type", "affinity"];
+    titles.extend(STORAGE_CLASSES.iter());
+    titles.extend(["mismatched", "distinct", "min", "max", "most common"].iter());
+    table.set_titles(Row::new(titles.into_iter().map(Cell::new).collect()));
+
+    for report in reports {
+        let mut cells = vec![
+            Cell::new(&report.column),
+            Cell::new(&report.sql_type),
+            Cell::new(report.affinity.name()),
+        ];
+
+        for (class, count) in STORAGE_CLASSES.iter().zip(report.storage_counts.iter()) {
+            let cell = Cell::new(&count.to_string());
+            cells.push(if *count > 0 && !is_expected(report.affinity, class) { cell.style_spec("Fr") } else { cell });
+        }
+
+        let most_common: Vec<String> = report.most_common.iter()
+            .map(|(value, count)| format!("{} ({})", value_repr(config, value), count))
+            .collect();
+
+        cells.push(Cell::new(&report.mismatched().to_string()));
+        cells.push(Cell::new(&report.distinct.to_string()));
+        cells.push(Cell::new(&value_repr(config, &report.min)));
+        cells.push(Cell::new(&value_repr(config, &report.max)));
+        cells.push(Cell::new(&most_common.join(", ")));
+
+        table.add_row(Row::new(cells));
+    }
+
+    table.printstd();
+}
+
+// Text columns get numbers cast to text. Other affinities get text cast by sqlite itself, only
+// where casting it back gives the same text, so nothing is rounded, truncated or read as NULL.
+// Updates match on values instead of rowid, which WITHOUT ROWID tables don't have.
+// Returns count of converted and skipped values.
+fn convert_mismatched(connection: &mut Connection, table: &str, reports: &[&ColumnReport]) -> Result<(usize, usize)> {
+    let transaction = connection.transaction()?;
+    let mut converted = 0;
+    let mut skipped = 0;
+
+    for report in reports {
+        let (column, quoted_table) = (quote_identifier(&report.column), quote_identifier(table));
+
+        let sql = match report.affinity {
+            Affinity::Text => format!(
+                "UPDATE {1} SET {0} = CAST({0} AS TEXT) WHERE typeof({0}) IN ('integer', 'real')",
+                column, quoted_table
+            ),
+            // CAST(text AS NUMERIC) gives an integer whenever it is lossless
+            Affinity::Integer => format!(
+                "UPDATE {1} SET {0} = CAST({0} AS NUMERIC) WHERE typeof({0}) = 'text' \
+                    AND typeof(CAST({0} AS NUMERIC)) = 'integer' \
+                    AND CAST(CAST({0} AS NUMERIC) AS TEXT) = CAST({0} AS TEXT)",
+                column, quoted_table
+            ),
+            Affinity::Real => format!(
+                "UPDATE {1} SET {0} = CAST({0} AS REAL) WHERE (typeof({0}) = 'text' \
+                    AND CAST(CAST({0} AS NUMERIC) AS TEXT) = CAST({0} AS TEXT)) \
+                    OR (typeof({0}) = 'integer' AND CAST(CAST({0} AS REAL) AS INTEGER) = {0})",
+                column, quoted_table
+            ),
+            Affinity::Numeric => format!(
+                "UPDATE {1} SET {0} = CAST({0} AS NUMERIC) WHERE typeof({0}) = 'text' \
+                    AND CAST(CAST({0} AS NUMERIC) AS TEXT) = CAST({0} AS TEXT)",
+                column, quoted_table
+            ),
+            // Every storage class is expected, nothing gets here
+            Affinity::Blob => continue,
+        };
+
+        transaction.execute(&sql, NO_PARAMS).map_err(|err| Error::in_statement(err, &sql))?;
+    }
+
+    // Rewriting a row applies affinity to all of its columns, so values are counted again
+    // instead of summing changed rows
+    for report in reports {
+        let left = count_mismatched(report.affinity, &count_storage_classes(&transaction, table, &report.column)?);
+
+        converted += (report.mismatched() - left) as usize;
+        skipped += left as usize;
+    }
+
+    transaction.commit()?;
+
+    Ok((converted, skipped))
+}
@@ -0,0 +1,693 @@
+// Database logic shared by every frontend. Nothing here prompts or prints, functions take a
+// connection and return data or statements ready to be confirmed and executed.
+
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension, ToSql, NO_PARAMS, params, types::Value};
+
+use crate::error::{Error, Result};
+use crate::utils::did_you_mean;
+
+/// Column as declared in the schema. Columns declared without a type have an empty `sqltype`.
+pub struct Column {
+    pub name: String,
+    pub sqltype: String,
+    pub not_null: bool,
+    pub default: Option<String>,
+    pub primary_key: bool,
+}
+
+pub struct ResultColumn {
+    pub name: String,
+    pub decl_type: Option<String>,
+}
+
+/// Rows read into memory, so they can be drawn after the statement is gone.
+pub struct ResultSet {
+    pub columns: Vec<ResultColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// SQL text with values bound to its `?` placeholders in order.
+pub struct Query {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl Query {
+    pub fn new(sql: String, params: Vec<Value>) -> Self {
+        Self { sql, params }
+    }
+
+    pub fn execute(&self, connection: &Connection) -> Result<usize> {
+        connection.execute(&self.sql, &self.params).map_err(|err| Error::in_statement(err, &self.sql))
+    }
+
+    /// SQL with placeholders replaced by literals, for showing to the user before execution.
+    pub fn preview(&self) -> String {
+        let mut preview = String::with_capacity(self.sql.len());
+        let mut params = self.params.iter();
+        let mut quote: Option<char> = None;
+
+        for c in self.sql.chars() {
+            match (quote, c) {
+                (None, '\'') | (None, '"') => { quote = Some(c); preview.push(c); },
+                (Some(q), _) if q == c => { quote = None; preview.push(c); },
+                (None, '?') => match params.next() {
+                    Some(value) => preview.push_str(&to_sqlite_literal(value)),
+                    None => preview.push(c),
+                },
+                _ => preview.push(c),
+            }
+        }
+
+        preview
+    }
+}
+
+pub fn table_exists(connection: &Connection, name: &str) -> Result<bool> {
+    let mut statement = connection.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=?")?;
+    Ok(statement.exists(params![name])?)
+}
+
+pub fn table_names(connection: &Connection) -> Result<Vec<String>> {
+    let mut statement = connection.prepare(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+    )?;
+    let names = statement.query_map(NO_PARAMS, |row| row.get(0))?;
+
+    Ok(names.collect::<rusqlite::Result<Vec<String>>>()?)
+}
+
+pub fn table_columns(connection: &Connection, table: &str) -> Result<Vec<Column>> {
+    table_columns_in(connection, "main", table)
+}
+
+/// Columns of a table in an attached database, `schema` is the name given in ATTACH.
+pub fn table_columns_in(connection: &Connection, schema: &str, table: &str) -> Result<Vec<Column>> {
+    let mut statement = connection.prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?, ?) ORDER BY cid")?;
+    let columns = statement.query_map(params![table, schema], |row| {
+        Ok(Column {
+            name: row.get(0)?,
+            sqltype: row.get(1)?,
+            not_null: row.get(2)?,
+            default: row.get(3)?,
+            primary_key: row.get::<usize, i64>(4)? > 0,
+        })
+    })?.collect::<rusqlite::Result<Vec<Column>>>()?;
+
+    if columns.is_empty() {
+        let names = fetch(connection, &format!("SELECT name FROM {}.sqlite_master WHERE type IN ('table', 'view')", quote_identifier(schema)), &[])
+            .map(|result| result.rows.into_iter().filter_map(|row| match row.into_iter().next() {
+                Some(Value::Text(name)) => Some(name),
+                _ => None,
+            }).collect())
+            .unwrap_or_else(|_| Vec::new());
+        let suggestion = did_you_mean(table, names.iter().map(String::as_str));
+
+        return Err(Error::validation(format!("Table '{}' has no columns or does not exist.{}", table, suggestion)));
+    }
+
+    Ok(columns)
+}
+
+pub fn fetch(connection: &Connection, sql: &str, params: &[&dyn ToSql]) -> Result<ResultSet> {
+    let in_statement = |err| Error::in_statement(err, sql);
+
+    let mut statement = connection.prepare(sql).map_err(in_statement)?;
+    let columns = statement.columns().iter()
+        .map(|c| ResultColumn { name: c.name().to_string(), decl_type: c.decl_type().map(String::from) })
+        .collect();
+    let column_count = statement.column_count();
+
+    let mut rows = statement.query(params).map_err(in_statement)?;
+    let mut values = Vec::new();
+    while let Some(row) = rows.next().map_err(in_statement)? {
+        values.push((0..column_count).map(|i| row.get(i)).collect::<rusqlite::Result<Vec<Value>>>().map_err(in_statement)?);
+    }
+
+    Ok(ResultSet { columns, rows: values })
+}
+
+/// One page of a table, `sorting` lists columns with true for ascending order.
+pub fn fetch_page(connection: &Connection, table: &str, sorting: &[(&str, bool)], page_size: usize, page: usize) -> Result<ResultSet> {
+    fetch_range(connection, table, sorting, page * page_size, page_size)
+}
+
+pub fn fetch_range(connection: &Connection, table: &str, sorting: &[(&str, bool)], offset: usize, limit: usize) -> Result<ResultSet> {
+    fetch_view(connection, table, &[], None, sorting, offset, limit)
+}
+
+/// Like fetch_range with `extra` columns given as (label, expression) after the table columns.
+/// `condition` is raw SQL placed after WHERE, it can use the labels as sqlite resolves aliases there.
+pub fn fetch_view(
+    connection: &Connection, table: &str, extra: &[(String, String)], condition: Option<&str>,
+    sorting: &[(&str, bool)], offset: usize, limit: usize,
+) -> Result<ResultSet> {
+    let mut sql = String::from("SELECT *");
+    for (label, expression) in extra {
+        sql.push_str(&format!(", {} AS {}", expression, quote_identifier(label)));
+    }
+    sql.push_str(&format!(" FROM {}", quote_identifier(table)));
+    if let Some(condition) = condition {
+        sql.push_str(" WHERE ");
+        sql.push_str(condition);
+    }
+    sql.push_str(&order_by(sorting));
+
+    // LIMIT with OFFSET is not optimal as it reads all skipped rows anyway.
+    // TODO: optimize it by using WHERE condition
+    sql.push_str(" LIMIT ? OFFSET ?");
+
+    fetch(connection, &sql, params![limit as i64, offset as i64])
+}
+
+/// ` ORDER BY ...` for the columns, true for ascending order. Empty when there is nothing to sort by.
+pub fn order_by(sorting: &[(&str, bool)]) -> String {
+    if sorting.is_empty() {
+        return String::new();
+    }
+
+    let terms: Vec<String> = sorting.iter()
+        .map(|(column, ascending)| format!("{} {}", quote_identifier(column), if *ascending { "ASC" } else { "DESC" }))
+        .collect();
+
+    format!(" ORDER BY {}", terms.join(", "))
+}
+
+/// `json_extract(column, path)` for reading a value out of JSON text.
+pub fn json_extract(column: &str, path: &str) -> String {
+    format!("json_extract({}, {})", quote_identifier(column), to_sqlite_literal(&Value::Text(path.to_string())))
+}
+
+/// Columns declared as JSON or checked with json_valid() in the table definition.
+pub fn json_columns(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    lazy_static! {
+        static ref JSON_VALID: Regex = Regex::new(r#"(?i)json_valid\s*\(\s*("(?:[^"]|"")*"|\[[^\]]*\]|`[^`]*`|\w+)\s*\)"#).unwrap();
+    }
+
+    let sql: String = connection
+        .query_row("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?", params![table], |row| row.get(0))
+        .optional()?
+        .unwrap_or_default();
+    let checked: Vec<String> = JSON_VALID.captures_iter(&sql)
+        .map(|captures| unquote_identifier(&captures[1]))
+        .collect();
+
+    Ok(table_columns(connection, table)?.into_iter()
+        .filter(|column| column.sqltype.eq_ignore_ascii_case("JSON") || checked.iter().any(|name| name.eq_ignore_ascii_case(&column.name)))
+        .map(|column| column.name)
+        .collect())
+}
+
+/// Keys found in JSON objects stored in the column, at most `limit` of them.
+pub fn json_object_keys(connection: &Connection, table: &str, column: &str, limit: usize) -> Result<Vec<String>> {
+    let (table, column) = (quote_identifier(table), quote_identifier(column));
+    let sql = format!(
+        "SELECT DISTINCT entry.key FROM {0}, json_each({0}.{1}) AS entry \
+         WHERE json_valid({0}.{1}) AND json_type({0}.{1}) = 'object' LIMIT ?",
+        table, column
+    );
+
+    let mut statement = connection.prepare(&sql).map_err(|err| Error::in_statement(err, &sql))?;
+    let keys = statement.query_map(params![limit as i64], |row| row.get(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+        .map_err(|err| Error::in_statement(err, &sql))?;
+
+    Ok(keys)
+}
+
+// Name from "name", [name], `name` or a bare word
+fn unquote_identifier(text: &str) -> String {
+    match text.chars().next() {
+        Some('"') => text[1..text.len() - 1].replace("\"\"", "\""),
+        Some('[') | Some('`') => text[1..text.len() - 1].to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// Rows in rowid order together with their rowid, so they can be changed in place.
+/// Fails for views and WITHOUT ROWID tables.
+pub fn fetch_range_with_rowid(connection: &Connection, table: &str, offset: usize, limit: usize) -> Result<Vec<(i64, Vec<Value>)>> {
+    let sql = format!("SELECT rowid, * FROM {} ORDER BY rowid LIMIT ? OFFSET ?", quote_identifier(table));
+    let result = fetch(connection, &sql, params![limit as i64, offset as i64])?;
+
+    let rows = result.rows.into_iter()
+        .map(|mut values| {
+            let rowid = match values.remove(0) {
+                Value::Integer(rowid) => rowid,
+                _ => 0,
+            };
+            (rowid, values)
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+pub fn count_rows(connection: &Connection, table: &str) -> Result<usize> {
+    let sql = format!("SELECT COUNT(*) FROM {}", quote_identifier(table));
+    let count: i64 = connection.query_row(&sql, NO_PARAMS, |row| row.get(0)).map_err(|err| Error::in_statement(err, &sql))?;
+
+    Ok(count as usize)
+}
+
+/// Page layout and journal settings read from pragmas.
+pub struct StorageInfo {
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    pub journal_mode: String,
+}
+
+impl StorageInfo {
+    /// Size of the main database file, pages are only written back in full.
+    pub fn size(&self) -> u64 {
+        (self.page_size * self.page_count) as u64
+    }
+}
+
+pub fn storage_info(connection: &Connection) -> Result<StorageInfo> {
+    let pragma = |name: &str| connection.query_row(&format!("PRAGMA {}", name), NO_PARAMS, |row| row.get::<usize, Value>(0));
+
+    let integer = |value| match value {
+        Value::Integer(i) => i,
+        _ => 0,
+    };
+
+    Ok(StorageInfo {
+        page_size: integer(pragma("page_size")?),
+        page_count: integer(pragma("page_count")?),
+        freelist_count: integer(pragma("freelist_count")?),
+        journal_mode: match pragma("journal_mode")? {
+            Value::Text(mode) => mode,
+            _ => String::new(),
+        },
+    })
+}
+
+/// Columns of storage_report, for choosing the sort order.
+pub const STORAGE_REPORT_COLUMNS: [&str; 7] = ["name", "type", "pages", "bytes", "unused", "avg_row_size", "fragmentation"];
+
+// Pages of a b-tree are fragmented when the next one in tree order is not the next one in the file,
+// the same measure as in sqlite3_analyzer. Average row size counts only entries on leaf pages for
+// tables, their interior pages hold just keys.
+const STORAGE_REPORT: &str = "
+WITH pages AS (
+    SELECT s.name, coalesce(m.type, 'table') AS type, s.pageno, s.pagetype, s.ncell, s.payload, s.unused, s.pgsize,
+        lag(s.pageno) OVER (PARTITION BY s.name ORDER BY s.path) AS previous
+    FROM dbstat AS s LEFT JOIN sqlite_master AS m ON m.name = s.name
+)
+SELECT * FROM (
+    SELECT name, type, COUNT(*) AS pages, SUM(pgsize) AS bytes, SUM(unused) AS unused,
+        round(SUM(payload) * 1.0 / nullif(SUM(CASE WHEN pagetype = 'leaf' OR type = 'index' THEN ncell ELSE 0 END), 0), 1) AS avg_row_size,
+        round(100.0 * SUM(previous IS NOT NULL AND pageno <> previous + 1) / COUNT(*), 1) AS fragmentation
+    FROM pages
+    GROUP BY name
+)";
+
+/// Space taken by every table and index, needs sqlite built with the dbstat virtual table.
+pub fn storage_report(connection: &Connection, sorting: &[(&str, bool)]) -> Result<ResultSet> {
+    let sql = format!("{}{}", STORAGE_REPORT, order_by(sorting));
+
+    fetch(connection, &sql, &[]).map_err(|err| match err {
+        Error::Sqlite { source: rusqlite::Error::SqliteFailure(_, Some(ref message)), .. } if message.contains("no such table: dbstat") => {
+            Error::validation("Storage report needs sqlite built with the dbstat virtual table (SQLITE_ENABLE_DBSTAT_VTAB)")
+        },
+        err => err,
+    })
+}
+
+/// Returns the mode in effect afterwards, sqlite keeps the old one when the switch is not possible,
+/// for example WAL for in-memory databases.
+pub fn set_journal_mode(connection: &Connection, mode: JournalMode) -> Result<String> {
+    let sql = format!("PRAGMA journal_mode = {}", mode.name());
+    connection.query_row(&sql, NO_PARAMS, |row| row.get(0)).map_err(|err| Error::in_statement(err, &sql))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JournalMode {
+    Delete,
+    Wal,
+}
+
+impl JournalMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Wal => "WAL",
+        }
+    }
+}
+
+/// Whether the statement leaves the database unchanged, decided by sqlite from the compiled
+/// statement, so DELETE ... RETURNING or a WITH ending in UPDATE are not mistaken for queries.
+pub fn is_read_only(connection: &Connection, sql: &str) -> Result<bool> {
+    use std::ffi::CString;
+    use std::ptr;
+    use rusqlite::ffi;
+
+    // Reports errors with the statement like every other function here
+    connection.prepare(sql).map_err(|err| Error::in_statement(err, sql))?;
+
+    let text = CString::new(sql).map_err(|_| Error::validation("Statement cannot contain NUL character"))?;
+    unsafe {
+        let mut statement = ptr::null_mut();
+        if ffi::sqlite3_prepare_v2(connection.handle(), text.as_ptr(), -1, &mut statement, ptr::null_mut()) != ffi::SQLITE_OK {
+            return Ok(false);
+        }
+
+        // Text with only comments compiles to no statement
+        let read_only = statement.is_null() || ffi::sqlite3_stmt_readonly(statement) != 0;
+        ffi::sqlite3_finalize(statement);
+        Ok(read_only)
+    }
+}
+
+/// Writes a compacted copy of the database into a new file.
+pub fn vacuum_into(connection: &Connection, path: &str) -> Result<()> {
+    let sql = "VACUUM INTO ?";
+    connection.execute(sql, params![path]).map_err(|err| Error::in_statement(err, sql))?;
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub sql_type: String,
+    pub default: Option<Value>,
+    // Adds CHECK(json_valid(...)), so only JSON text or NULL can be stored
+    pub json: bool,
+}
+
+/// Table being put together column by column before it is created.
+#[derive(Default)]
+pub struct TableDefinition {
+    pub name: Option<String>,
+    pub columns: Vec<ColumnDefinition>,
+}
+
+impl TableDefinition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_name(&mut self, name: &str) -> &mut Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn add_column(&mut self, column: ColumnDefinition) -> &mut Self {
+        self.columns.push(column);
+        self
+    }
+
+    pub fn column_mut(&mut self, name: &str) -> Result<&mut ColumnDefinition> {
+        self.columns.iter_mut()
+            .find(|column| column.name == name)
+            .ok_or_else(|| Error::validation(format!("Column '{}' is not found in the table", name)))
+    }
+
+    pub fn remove_column(&mut self, name: &str) -> Result<ColumnDefinition> {
+        let pos = self.columns.iter().position(|column| column.name == name)
+            .ok_or_else(|| Error::validation(format!("Column '{}' is not found in the table", name)))?;
+
+        Ok(self.columns.remove(pos))
+    }
+
+    // Defaults are written as literals, sqlite does not take parameters in DDL
+    pub fn create_query(&self) -> Result<Query> {
+        let name = self.name.as_ref().ok_or_else(|| Error::validation("Table has no name"))?;
+
+        if self.columns.is_empty() {
+            return Err(Error::validation("Table needs at least one column"));
+        }
+
+        let columns: Vec<String> = self.columns.iter()
+            .map(|column| {
+                let mut definition = format!("{} {}", quote_identifier(&column.name), column.sql_type);
+                if let Some(default) = &column.default {
+                    definition.push_str(" DEFAULT ");
+                    definition.push_str(&to_sqlite_literal(default));
+                }
+                if column.json {
+                    definition.push_str(&format!(" CHECK(json_valid({}))", quote_identifier(&column.name)));
+                }
+                definition
+            })
+            .collect();
+
+        Ok(Query::new(format!("CREATE TABLE IF NOT EXISTS {}({})", quote_identifier(name), columns.join(",")), Vec::new()))
+    }
+}
+
+pub fn insert(table: &str, values: Vec<Value>) -> Query {
+    let placeholders = vec!["?"; values.len()].join(",");
+    Query::new(format!("INSERT INTO {} VALUES ({})", quote_identifier(table), placeholders), values)
+}
+
+/// Insert naming its columns, for rows which do not set every column of the table.
+pub fn insert_columns(table: &str, values: Vec<(String, Value)>) -> Query {
+    let (columns, values): (Vec<String>, Vec<Value>) = values.into_iter()
+        .map(|(column, value)| (quote_identifier(&column), value))
+        .unzip();
+    let placeholders = vec!["?"; values.len()].join(",");
+
+    Query::new(format!("INSERT INTO {} ({}) VALUES ({})", quote_identifier(table), columns.join(","), placeholders), values)
+}
+
+// Why (x+1)||1
+// Mostly beacuse doing 'a'+1 is considered 1
+// x'00' || 1 is NULL
+// combination (x+1)||1 mostly works for all literals
+
+/// Inserts a row where every column is one step past the current maximum.
+pub fn insert_consecutive(table: &str, columns: &[Column]) -> Query {
+    let next_values: Vec<String> = columns.iter()
+        .map(|column| format!("MAX(({}+1)||1)", quote_identifier(&column.name)))
+        .collect();
+
+    let table = quote_identifier(table);
+    Query::new(format!("INSERT INTO {} SELECT {} FROM {}", table, next_values.join(", "), table), Vec::new())
+}
+
+/// `condition` is raw SQL placed after WHERE, None reads every row.
+pub fn select(table: &str, condition: Option<&str>, sorting: &[(&str, bool)], limit: Option<usize>) -> Query {
+    let mut sql = format!("SELECT * FROM {}", quote_identifier(table));
+    if let Some(condition) = condition {
+        sql.push_str(" WHERE ");
+        sql.push_str(condition);
+    }
+    sql.push_str(&order_by(sorting));
+    if let Some(limit) = limit {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    Query::new(sql, Vec::new())
+}
+
+/// `condition` is raw SQL placed after WHERE, None changes every row.
+pub fn update(table: &str, assignments: Vec<(String, Value)>, condition: Option<&str>) -> Query {
+    let (columns, values): (Vec<String>, Vec<Value>) = assignments.into_iter()
+        .map(|(column, value)| (format!("{} = ?", quote_identifier(&column)), value))
+        .unzip();
+
+    let mut sql = format!("UPDATE {} SET {}", quote_identifier(table), columns.join(", "));
+    if let Some(condition) = condition {
+        sql.push_str(" WHERE ");
+        sql.push_str(condition);
+    }
+
+    Query::new(sql, values)
+}
+
+/// `condition` is raw SQL placed after WHERE, None removes every row.
+pub fn delete(table: &str, condition: Option<&str>) -> Query {
+    let mut sql = format!("DELETE FROM {}", quote_identifier(table));
+    if let Some(condition) = condition {
+        sql.push_str(" WHERE ");
+        sql.push_str(condition);
+    }
+
+    Query::new(sql, Vec::new())
+}
+
+/// Puts a table or column name in double quotes, so names with spaces, quotes, Unicode or reserved
+/// words can be used in generated statements.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn to_sqlite_literal(val: &Value) -> String {
+    use rusqlite::types::Value::*;
+
+    match val {
+        Null => "NULL".to_string(),
+        Integer(i) => format!("{}", i),
+        // Debug formatting always writes a decimal point or an exponent, so it is read back as REAL
+        Real(f) if f.is_nan() => "NULL".to_string(),
+        Real(f) if f.is_infinite() => String::from(if *f > 0.0 { "1e999" } else { "-1e999" }),
+        Real(f) => format!("{:?}", f),
+        Text(t) => format!("'{}'", t.replace('\'', "''")),
+        Blob(v) => {
+            let hex: String = v.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("X'{}'", hex)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_value(connection: &Connection, sql: &str) -> Value {
+        connection.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn quote_identifier_doubles_quotes() {
+        assert_eq!(quote_identifier("name"), "\"name\"");
+        assert_eq!(quote_identifier("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn literals_are_read_back_as_the_same_value() {
+        let connection = Connection::open_in_memory().unwrap();
+        let values = vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(i64::MIN),
+            Value::Integer(i64::MAX),
+            Value::Real(0.1),
+            Value::Real(-2.5e-300),
+            Value::Real(1.0),
+            Value::Real(f64::INFINITY),
+            Value::Text(String::from("it's")),
+            Value::Text(String::new()),
+            Value::Blob(vec![0, 1, 0xab, 0xff]),
+        ];
+
+        for value in values {
+            let literal = to_sqlite_literal(&value);
+            assert_eq!(single_value(&connection, &format!("SELECT {}", literal)), value, "{}", literal);
+        }
+    }
+
+    #[test]
+    fn nan_is_written_as_null() {
+        assert_eq!(to_sqlite_literal(&Value::Real(f64::NAN)), "NULL");
+    }
+
+    #[test]
+    fn preview_fills_placeholders_outside_quotes() {
+        let query = Query::new(
+            String::from("SELECT '?', \"a?\", ?, ?"),
+            vec![Value::Text(String::from("x")), Value::Integer(2)],
+        );
+        assert_eq!(query.preview(), "SELECT '?', \"a?\", 'x', 2");
+    }
+
+    #[test]
+    fn preview_keeps_placeholders_without_values() {
+        let query = Query::new(String::from("SELECT ?, ?"), vec![Value::Null]);
+        assert_eq!(query.preview(), "SELECT NULL, ?");
+    }
+
+    #[test]
+    fn table_definition_needs_name_and_columns() {
+        let mut table = TableDefinition::new();
+        assert!(table.create_query().is_err());
+
+        table.set_name("t");
+        assert!(table.create_query().is_err());
+
+        table.add_column(ColumnDefinition { name: String::from("a"), sql_type: String::from("INTEGER"), ..Default::default() });
+        assert!(table.create_query().is_ok());
+    }
+
+    #[test]
+    fn table_definition_creates_table_with_unusual_names() {
+        let connection = Connection::open_in_memory().unwrap();
+        let mut table = TableDefinition::new();
+        table.set_name("my \"table\"")
+            .add_column(ColumnDefinition {
+                name: String::from("select"),
+                sql_type: String::from("TEXT"),
+                default: Some(Value::Text(String::from("it's"))),
+                json: false,
+            })
+            .add_column(ColumnDefinition { name: String::from("doc"), sql_type: String::from("TEXT"), default: None, json: true });
+
+        table.create_query().unwrap().execute(&connection).unwrap();
+
+        let columns = table_columns(&connection, "my \"table\"").unwrap();
+        let names: Vec<&str> = columns.iter().map(|column| column.name.as_str()).collect();
+        assert_eq!(names, ["select", "doc"]);
+        assert_eq!(columns[0].default.as_deref(), Some("'it''s'"));
+
+        insert_columns("my \"table\"", vec![(String::from("doc"), Value::Text(String::from("{}")))]).execute(&connection).unwrap();
+        assert_eq!(single_value(&connection, "SELECT \"select\" FROM \"my \"\"table\"\"\""), Value::Text(String::from("it's")));
+
+        // json columns only take valid JSON
+        let invalid = insert_columns("my \"table\"", vec![(String::from("doc"), Value::Text(String::from("{")))]);
+        assert!(invalid.execute(&connection).is_err());
+    }
+
+    #[test]
+    fn remove_column_reports_missing_column() {
+        let mut table = TableDefinition::new();
+        table.add_column(ColumnDefinition { name: String::from("a"), ..Default::default() });
+
+        assert!(table.remove_column("b").is_err());
+        assert_eq!(table.remove_column("a").unwrap().name, "a");
+        assert!(table.columns.is_empty());
+    }
+
+    #[test]
+    fn builders_change_matching_rows() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("CREATE TABLE \"t t\"(a INTEGER, b TEXT)").unwrap();
+
+        for i in 1..=3 {
+            insert("t t", vec![Value::Integer(i), Value::Text(format!("row {}", i))]).execute(&connection).unwrap();
+        }
+        assert_eq!(count_rows(&connection, "t t").unwrap(), 3);
+
+        let updated = update("t t", vec![(String::from("b"), Value::Null)], Some("a > 1")).execute(&connection).unwrap();
+        assert_eq!(updated, 2);
+
+        let rows = fetch(&connection, &select("t t", Some("b IS NULL"), &[("a", false)], Some(1)).sql, &[]).unwrap();
+        assert_eq!(rows.rows, vec![vec![Value::Integer(3), Value::Null]]);
+
+        assert_eq!(delete("t t", Some("a = 1")).execute(&connection).unwrap(), 1);
+        assert_eq!(delete("t t", None).execute(&connection).unwrap(), 2);
+    }
+
+    #[test]
+    fn consecutive_insert_steps_past_maximum() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("CREATE TABLE t(a INTEGER, b TEXT); INSERT INTO t VALUES (4, 'x')").unwrap();
+
+        let columns = table_columns(&connection, "t").unwrap();
+        insert_consecutive("t", &columns).execute(&connection).unwrap();
+
+        assert_eq!(single_value(&connection, "SELECT MAX(a) FROM t"), Value::Integer(51));
+    }
+
+    #[test]
+    fn statements_returning_rows_can_change_the_database() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("CREATE TABLE t(a)").unwrap();
+
+        assert!(is_read_only(&connection, "SELECT * FROM t").unwrap());
+        assert!(is_read_only(&connection, "-- nothing").unwrap());
+        assert!(!is_read_only(&connection, "DELETE FROM t RETURNING *").unwrap());
+        assert!(!is_read_only(&connection, "WITH x AS (SELECT 1) UPDATE t SET a = 1 RETURNING a").unwrap());
+        assert!(is_read_only(&connection, "SELEC 1").is_err());
+    }
+
+    #[test]
+    fn order_by_lists_columns_in_order() {
+        assert_eq!(order_by(&[]), "");
+        assert_eq!(order_by(&[("a", true), ("b c", false)]), " ORDER BY \"a\" ASC, \"b c\" DESC");
+    }
+}
@@ -1,34 +1,16 @@
-use std::io;
-
 use console::style;
-use dialoguer::{Input, Select};
 
 use num_traits::FromPrimitive;
 
-use rusqlite::{params};
+use rusqlite::types::Value;
 
 use crate::App;
+use crate::db::{ColumnDefinition, TableDefinition, to_sqlite_literal};
+use crate::error::{Error, Result};
+use crate::prompt::{Confirmation, Input, Select};
 use crate::utils::*;
-
-struct TableDefinition {
-    name: Option<String>,
-    columns: Vec<ColumnDefinition>,
-}
-
-impl TableDefinition {
-    fn new() -> Self {
-        Self {
-            name: None,
-            columns: Vec::new(),
-        }
-    }
-}
-
-#[derive(Default)]
-struct ColumnDefinition {
-    name: String,
-    sql_type: String,
-}
+use crate::value_parser::{Affinity, parse_value, to_input, validate_value};
+use crate::history::record_query;
 
 #[derive(Clone)]
 enum DefineTablePromptOption {
@@ -39,12 +21,12 @@ enum DefineTablePromptOption {
     Cancel,
 }
 
-pub fn define_table(app: &mut App) {
-    let mut table = TableDefinition::new();
-    let mut query: String;
+pub fn define_table(app: &mut App) -> Result<()> {
+    app.require_writable()?;
 
-    loop {
-        clear();
+    let mut table = TableDefinition::new();
+    let query = loop {
+        clear()?;
 
         println!("Create new table\n");
 
@@ -52,43 +34,35 @@ pub fn define_table(app: &mut App) {
         println!("\n");
 
         use DefineTablePromptOption::*;
-        match define_table_prompt(app, &table).expect("IO error") {
-            SetName => { set_name(app, &mut table) },
-            AddColumn => { add_column(app, &mut table); },
-            SetColumn(name) => { clear(); update_or_delete_column(app, &mut table, name); }
+        match define_table_prompt(app, &table)? {
+            SetName => { set_name(app, &mut table)?; },
+            AddColumn => { add_column(app, &mut table)?; },
+            SetColumn(name) => { clear()?; update_or_delete_column(app, &mut table, name)?; }
             Create => {
-                query = create_query_from_definition(&table);
-                if ask_for_confirmation_before_query(app, &query) {
-                    break;
+                let query = table.create_query()?;
+                if ask_for_confirmation_before_query(app, &query.sql)? {
+                    break query;
                 }
             },
-            Cancel => { return; },
+            Cancel => { return Ok(()); },
         }
-    }
+    };
 
-    match app.connection.as_ref().unwrap().execute(query.as_str(), params![]) {
-        Ok(_) => {
-            match app.set_active_table(table.name.unwrap().as_str()) {
-                Ok(_) => {},
-                Err(err) => panic!(err),
-            }
-        },
-        Err(err) => {
-            println!("Could not execute a query! Reason: {}", err);
-            wait_for_keypress();
-        },
-    }
+    query.execute(app.require_connection()?)?;
+
+    record_query(app, &query.sql);
+    app.set_active_table(table.name.as_deref().unwrap_or_default())
 }
 
 
-fn define_table_prompt(app: &App, table: &TableDefinition) -> Result<DefineTablePromptOption, io::Error>{
+fn define_table_prompt(app: &App, table: &TableDefinition) -> Result<DefineTablePromptOption> {
     use DefineTablePromptOption::*;
 
     let mut select = Select::with_theme(&app.view.dialog_theme);
     let mut options: Vec<DefineTablePromptOption> = Vec::new();
 
     options.push(SetName);
-    if let Some(_) = table.name {
+    if table.name.is_some() {
         select.item("Change name");
 
         options.push(AddColumn);
@@ -112,49 +86,48 @@ fn define_table_prompt(app: &App, table: &TableDefinition) -> Result<DefineTable
     options.push(Cancel);
     select.item("Cancel");
 
-    match select.interact() {
-        Ok(option) => Ok(options.get(option).expect("define table option not in vector").clone()),
-        Err(err) => Err(err),
-    }
+    let option = select.interact()?;
+    Ok(options.get(option).expect("define table option not in vector").clone())
 }
 
-fn set_name(app: &App, table: &mut TableDefinition) {
+fn set_name(app: &App, table: &mut TableDefinition) -> Result<()> {
     println!("Set name for a table\n");
 
-    let default_name = if let Some(name) = &table.name {
-        name.clone()
-    } else {
-        String::from("")
-    };
-
-    let name = Input::with_theme(&app.view.dialog_theme)
-        .with_prompt("Set name")
-        .default(default_name)
-        .validate_with(ValidatorAdaptor::new(validate_table_name, format!("Table name must be alphanumeric")))
-        .interact().expect("IO error");
+    let mut input = Input::with_theme(&app.view.dialog_theme);
+    input.with_prompt("Set name")
+        .validate_with(ValidatorAdaptor::with_reason(validate_table_name));
+    if let Some(name) = &table.name {
+        input.default(name.clone());
+    }
+    let name: String = input.interact()?;
 
-    table.name = Some(name);
+    table.set_name(&name);
+    Ok(())
 }
 
-fn add_column(app: &App, table: &mut TableDefinition) {
+fn add_column(app: &App, table: &mut TableDefinition) -> Result<()> {
     let mut column = ColumnDefinition::default();
 
     println!("Adding new column\n");
 
     column.name = Input::with_theme(&app.view.dialog_theme)
         .with_prompt("Set column name")
-        .validate_with(ValidatorAdaptor::new(validate_column_name, String::from("Column name must be alphanumeric")))
-        .interact().expect("IO error");
+        .validate_with(ValidatorAdaptor::with_reason(validate_column_name))
+        .interact()?;
 
     column.sql_type = Input::with_theme(&app.view.dialog_theme)
         .with_prompt("Set column type with associated keywords")
         .validate_with(ValidatorAdaptor::new(validate_sql_type, String::from("SQL type must be alphanumeric")))
-        .interact().expect("IO error");
+        .interact()?;
+
+    column.default = ask_for_default(app, &column.sql_type, String::new())?;
+    column.json = ask_for_json(app, false)?;
 
-    table.columns.push(column);
+    table.add_column(column);
+    Ok(())
 }
 
-fn update_or_delete_column(app: &App, table: &mut TableDefinition, column_name: String) {
+fn update_or_delete_column(app: &App, table: &mut TableDefinition, column_name: String) -> Result<()> {
     println!("What do you want to do with \"{}\" column?\n", column_name);
 
     #[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive)]
@@ -170,39 +143,73 @@ fn update_or_delete_column(app: &App, table: &mut TableDefinition, column_name:
         .item("Modify definition")
         .item("Delete")
         .item("Nothing")
-        .interact().expect("IO error");
+        .interact()?;
 
     match Answer::from_usize(option).unwrap() {
         SetColumn => {
-            clear();
-            set_column(app, table, column_name);
-        }
-        Delete => {
-            let pos = table.columns.iter().position(|x| x.name == column_name)
-                .expect(format!("Column \'{}\'is not found in the table", column_name).as_str());
-            table.columns.drain(pos..pos+1);
+            clear()?;
+            set_column(app, table, column_name)?;
         }
-        Nothing => { return; }
+        Delete => { table.remove_column(&column_name)?; }
+        Nothing => {}
     }
 
+    Ok(())
 }
 
-fn set_column(app: &App, table: &mut TableDefinition, column_name: String) {
+fn set_column(app: &App, table: &mut TableDefinition, column_name: String) -> Result<()> {
     println!("Editing \"{}\" column\n", column_name);
 
-    let mut column: &mut ColumnDefinition = table.columns.iter_mut()
-        .find(|col| col.name == column_name)
-        .expect("Column not in vector");
+    let column = table.column_mut(&column_name)?;
 
     column.name = Input::with_theme(&app.view.dialog_theme)
         .with_prompt("Set column name")
         .default(column_name)
-        .interact().expect("IO error");
+        .validate_with(ValidatorAdaptor::with_reason(validate_column_name))
+        .interact()?;
 
     column.sql_type = Input::with_theme(&app.view.dialog_theme)
         .with_prompt("Set column type")
         .default(column.sql_type.clone())
-        .interact().expect("IO error");
+        .interact()?;
+
+    let affinity = Affinity::from_sql_type(&column.sql_type);
+    let current_default = match &column.default {
+        Some(default) => to_input(default, affinity).unwrap_or_else(|| {
+            println!("Current default {} cannot be written for {} affinity, leaving it empty removes it", to_sqlite_literal(default), affinity.name());
+            String::new()
+        }),
+        None => String::new(),
+    };
+    column.default = ask_for_default(app, &column.sql_type, current_default)?;
+    column.json = ask_for_json(app, column.json)?;
+    Ok(())
+}
+
+fn ask_for_json(app: &App, current: bool) -> Result<bool> {
+    Confirmation::with_theme(&app.view.dialog_theme)
+        .with_text("Does the column hold JSON? Other text will be rejected")
+        .default(current)
+        .interact()
+}
+
+fn ask_for_default(app: &App, sql_type: &str, current: String) -> Result<Option<Value>> {
+    let affinity = Affinity::from_sql_type(sql_type);
+    let show_current = !current.is_empty();
+
+    let default: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt(format!("Set default value ({} affinity, empty for none)", affinity.name()).as_str())
+        .default(current)
+        .show_default(show_current)
+        .allow_empty(true)
+        .validate_with(ValidatorAdaptor::with_reason(move |text| validate_value(text, affinity)))
+        .interact()?;
+
+    if default.trim().is_empty() {
+        Ok(None)
+    } else {
+        parse_value(&default, affinity).map(Some).map_err(Error::Validation)
+    }
 }
 
 fn print_preview(_app: &App, table_definition: &TableDefinition) {
@@ -222,22 +229,17 @@ fn print_preview(_app: &App, table_definition: &TableDefinition) {
     table.add_row(
         Row::new(columns.iter().map(|c| Cell::new(c.sql_type.as_str())).collect())
     );
-    table.printstd();
-}
-
-fn create_query_from_definition(table: &TableDefinition) -> String {
-    let mut query = String::from("CREATE TABLE IF NOT EXISTS ");
-    query.push_str(table.name.as_ref().unwrap().as_str());
-    query.push('(');
-    for (i, column) in table.columns.iter().enumerate() {
-        query.push_str(&column.name);
-        query.push(' ');
-        query.push_str(&column.sql_type);
-        if i < table.columns.len()-1 {
-            query.push(',');
-        }
+    if columns.iter().any(|c| c.default.is_some()) {
+        table.add_row(
+            Row::new(columns.iter().map(|c| {
+                Cell::new(c.default.as_ref().map(to_sqlite_literal).unwrap_or_default().as_str())
+            }).collect())
+        );
     }
-    query.push(')');
-
-    query
+    if columns.iter().any(|c| c.json) {
+        table.add_row(
+            Row::new(columns.iter().map(|c| Cell::new(if c.json { "JSON" } else { "" })).collect())
+        );
+    }
+    table.printstd();
 }
@@ -1,169 +1,340 @@
 use crate::app::App;
+use crate::blob;
+use crate::json;
+use crate::config::{Config, OutputFormat};
+use crate::error::{Error, Result};
 
-use rusqlite::{Rows, ToSql, NO_PARAMS, types::Value};
+use console::style;
+use rusqlite::{ToSql, types::Value};
 
-use prettytable::{Table, cell};
+use crate::utils::{ clear, truncate, print_error, wait_for_keypress, ValidatorAdaptor, CONDITION_KEYWORDS };
 
-use crate::utils::{ wait_for_keypress, clear, truncate, ask_for_confirmation_before_query };
-
-use dialoguer::{ Select, Input };
-
-use num_traits::FromPrimitive;
+use crate::prompt::{ Select, Input };
 
 use std::collections::HashMap;
 
-use crate::insert_row::{ Column, get_table };
-
-pub fn draw_query(app: &App, query: &str, params: &[&dyn ToSql]) -> Result<(), String> {
-    let connection = app.connection.as_ref().ok_or(format!("No connection is set to sqlite"))?;
-
-    let mut statement = match connection.prepare(query) {
-        Ok(stmt) => stmt,
-        Err(err) => {
-            return Err(format!("Could not display table {}", err));
-        }
-    };
-
-    let mut rows = match statement.query(params) {
-        Ok(rs) => rs,
-        Err(err) => {
-            return Err(format!("Failed to execute query. {}", err));
-        },
-    };
-
-    draw_from_rows(&mut rows).map_err(|err| format!("{}", err))?;
+use crate::db::{ self, ResultSet };
+use crate::insert_row::get_table;
+use crate::history::record_query;
+use crate::impact::confirm_with_preview;
+use crate::search::search_table;
+use crate::summary::summarize;
+use crate::sql::pretty;
+
+pub fn draw_query(app: &App, query: &str, params: &[&dyn ToSql]) -> Result<()> {
+    let result = db::fetch(app.require_connection()?, query, params)?;
+    draw_result(&app.config, &result)
+}
 
-    Ok(())
+pub fn draw_result(config: &Config, result: &ResultSet) -> Result<()> {
+    draw_result_with(config, result, |_, value| value_repr(config, value))
 }
 
-fn draw_from_rows(rows: &mut Rows) -> rusqlite::Result<()> {
+/// Like draw_result with cells of the grid given by `repr`, which gets the column index and value.
+/// CSV output always has the full text.
+pub fn draw_result_with<F: Fn(usize, &Value) -> String>(config: &Config, result: &ResultSet, repr: F) -> Result<()> {
     use prettytable::*;
 
     let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    match config.output_format {
+        OutputFormat::Table => table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR),
+        OutputFormat::Plain | OutputFormat::Csv => table.set_format(*format::consts::FORMAT_CLEAN),
+    }
 
     table.set_titles(
-        rows.columns().unwrap_or(Vec::new())
-        .iter().map(|c| cell!(format!("{}:{}",c.name(), c.decl_type().unwrap_or("none"))))
+        result.columns
+        .iter().map(|c| cell!(format!("{}:{}", c.name, c.decl_type.as_deref().unwrap_or("none"))))
         .collect()
     );
 
-    while let Some(row) = rows.next()? {
-        let mut i = 0;
-        let mut cells: Vec<Cell> = Vec::new();
-
-        while let Ok(data) = row.get::<usize, Value>(i) {
-            cells.push(cell!(value_repr(&data)));
-            i += 1;
-        }
+    for row in &result.rows {
+        let cells: Vec<Cell> = row.iter().enumerate()
+            .map(|(i, data)| match config.output_format {
+                OutputFormat::Csv => cell!(value_text(config, data)),
+                _ => cell!(repr(i, data)),
+            })
+            .collect();
 
         table.add_row(Row::new(cells));
     }
 
-    table.printstd();
+    if config.output_format == OutputFormat::Csv {
+        table.to_csv(std::io::stdout()).map_err(|err| Error::Io(err.into()))?;
+    } else {
+        table.printstd();
+    }
 
     Ok(())
 }
 
-pub fn draw_paginate(app: &App, at_once: usize, page: usize, sort_options: &HashMap<String, bool>) -> Result<(), String>{
-    // LIMIT x, y is not optimal as it reads whole table anyway.
-    // TODO: optimize it by using WHERE condition
-    let name = app.active_table().ok_or(format!("No active table was defined"))?;
+pub fn draw_paginate(app: &App, at_once: usize, page: usize, view: &TableView) -> Result<()> {
+    let sorting = view.sorting();
 
-    let sorting_query_part = {
-        if sort_options.is_empty() {
-            String::default()
-        } else {
-            let mut query = String::from("ORDER BY");
-            let option_count = sort_options.len();
-
-            for (i, (col, ascending)) in sort_options.iter().enumerate() {
-                let direction = if *ascending { "ASC" } else { "DESC" };
-                query.push_str(format!(" {} {}", col, direction).as_str());
-                if i < option_count-1 {
-                    query.push(',');
-                }
-            }
+    if let Some(condition) = &view.condition {
+        println!("WHERE {}", condition);
+    }
+    if !sorting.is_empty() {
+        println!("{}", db::order_by(&sorting).trim_start());
+    }
 
-            query
-        }
-    };
+    let result = view.fetch(app, page * at_once, at_once)?;
+    draw_result(&app.config, &result)
+}
 
-    println!("{}", sorting_query_part);
+/// Sorting, filter and computed columns chosen in display_table.
+#[derive(Default)]
+pub struct TableView {
+    sorting: HashMap<String, bool>,
+    condition: Option<String>,
+    // Label and expression of columns read out of JSON text
+    extra_columns: Vec<(String, String)>,
+}
 
-    draw_query(app, format!("SELECT * FROM {} {} LIMIT {}, {}", name, sorting_query_part, page*at_once, (page+1)*at_once).as_str(), NO_PARAMS)?;
+impl TableView {
+    fn sorting(&self) -> Vec<(&str, bool)> {
+        self.sorting.iter().map(|(column, ascending)| (column.as_str(), *ascending)).collect()
+    }
 
-    Ok(())
+    fn fetch(&self, app: &App, offset: usize, limit: usize) -> Result<ResultSet> {
+        db::fetch_view(
+            app.require_connection()?, app.require_active_table()?, &self.extra_columns,
+            self.condition.as_deref(), &self.sorting(), offset, limit,
+        )
+    }
+
+    fn labels(&self) -> impl Iterator<Item = &String> {
+        self.extra_columns.iter().map(|(label, _)| label)
+    }
+
+    fn add_column(&mut self, label: String, expression: String) {
+        if !self.labels().any(|existing| *existing == label) {
+            self.extra_columns.push((label, expression));
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum DisplayAnswer{
-    NextPage = 0,
-    PreviousPage = 1,
-    DefineSorting = 2,
-    DeleteRows = 3,
-    GoBack = 4,
+    NextPage,
+    PreviousPage,
+    ShowRow,
+    DefineSorting,
+    Filter,
+    Search,
+    Summarize,
+    AddJsonPath,
+    ExpandJson,
+    RemoveJsonColumns,
+    DeleteRows,
+    GoBack,
 }
 
 
-pub fn display_table(app: &App) {
-    static ROWS_PER_PAGE: usize = 50;
-    let mut starting_row: usize = 0;
+pub fn display_table(app: &App) -> Result<()> {
+    let rows_per_page = app.config.page_size;
+    let mut page: usize = 0;
     let mut last_chosen = 0;
-    let mut sorting_options: HashMap<String, bool> = HashMap::new();
+    let mut view = TableView::default();
 
-    if app.active_table().is_none() {
-        clear();
-        println!("No active table is chosen");
-        wait_for_keypress();
-        return;
-    }
+    clear()?;
+    let name = app.require_active_table()?;
 
-    let columns: Vec<Column> = {
-        match get_table(app) {
-            Ok(cols) => cols,
-            Err(err) => {
-                clear();
-                println!("{}", err);
-                wait_for_keypress();
-                return;
-            },
-        }
-    };
+    let columns: Vec<String> = get_table(app)?.into_iter().map(|column| column.name).collect();
+    let json_columns = db::json_columns(app.require_connection()?, name)?;
 
     loop {
-        clear();
+        clear()?;
 
-        println!("Rows from {} to {}", starting_row, starting_row+ROWS_PER_PAGE);
-
-        if let Err(err) = draw_paginate(app, ROWS_PER_PAGE, starting_row, &sorting_options) {
-            println!("Could display table!. {}", err);
-            wait_for_keypress();
-        }
+        println!("Rows from {} to {}", page*rows_per_page, (page+1)*rows_per_page);
 
-        println!("");
+        draw_paginate(app, rows_per_page, page, &view)?;
 
-        last_chosen = Select::with_theme(&app.view.dialog_theme)
-            .default(last_chosen)
-            .item("Next 50 rows")
-            .item("Previous 50 rows")
-            .item("Define sorting criteria")
-            .item("Delete rows on condition")
-            .item("Back")
-            .interact().expect("IO error");
+        println!();
 
         use DisplayAnswer::*;
-        match DisplayAnswer::from_usize(last_chosen).unwrap() {
-            NextPage => { starting_row += ROWS_PER_PAGE; },
-            PreviousPage => { if starting_row > 0 { starting_row -= ROWS_PER_PAGE; }; },
-            DefineSorting => { set_sorting_options(app, &columns, &mut sorting_options); },
-            DeleteRows => { delete_rows(app); },
+        let mut options = vec![NextPage, PreviousPage, ShowRow, DefineSorting, Filter, Search, Summarize, AddJsonPath, ExpandJson, RemoveJsonColumns, DeleteRows, GoBack];
+        if app.is_read_only() {
+            options.retain(|option| *option != DeleteRows);
+        }
+        if view.extra_columns.is_empty() {
+            options.retain(|option| *option != RemoveJsonColumns);
+        }
+        last_chosen = last_chosen.min(options.len() - 1);
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for option in &options {
+            match option {
+                NextPage => select.item(format!("Next {} rows", rows_per_page).as_str()),
+                PreviousPage => select.item(format!("Previous {} rows", rows_per_page).as_str()),
+                ShowRow => select.item("Show row details"),
+                DefineSorting => select.item("Define sorting criteria"),
+                Filter => select.item("Filter rows on condition"),
+                Search => select.item("Search"),
+                Summarize => select.item("Summarize with groups and aggregates"),
+                AddJsonPath => select.item("Add column from JSON path"),
+                ExpandJson => select.item("Expand JSON column into columns"),
+                RemoveJsonColumns => select.item("Remove columns read from JSON"),
+                DeleteRows => select.item("Delete rows on condition"),
+                GoBack => select.item("Back"),
+            };
+        }
+        last_chosen = select.interact()?;
+
+        // JSON columns are offered first, any text column may hold JSON without a check
+        let json_candidates = if json_columns.is_empty() { &columns } else { &json_columns };
+
+        match options[last_chosen] {
+            NextPage => { page += 1; },
+            PreviousPage => { page = page.saturating_sub(1); },
+            ShowRow => { show_row(app, &view, page * rows_per_page)?; },
+            DefineSorting => {
+                let sortable: Vec<String> = columns.iter().chain(view.labels()).cloned().collect();
+                set_sorting_options(app, &sortable, &mut view.sorting)?;
+            },
+            Filter => {
+                set_filter(app, &columns, &mut view)?;
+                page = 0;
+            },
+            Search => { search_table(app)?; },
+            Summarize => { summarize(app)?; },
+            AddJsonPath => { add_json_path(app, json_candidates, &mut view)?; },
+            ExpandJson => { expand_json(app, json_candidates, &mut view)?; },
+            RemoveJsonColumns => {
+                let labels: Vec<String> = view.labels().cloned().collect();
+                view.extra_columns.clear();
+                view.sorting.retain(|column, _| !labels.contains(column));
+                if view.condition.as_ref().is_some_and(|condition| labels.iter().any(|label| condition.contains(label.as_str()))) {
+                    view.condition = None;
+                }
+            },
+            DeleteRows => { delete_rows(app)?; },
             GoBack => { break; },
         }
     }
+
+    Ok(())
 }
 
-fn set_sorting_options(app: &App, columns: &Vec<Column>, sorting_options: &mut HashMap<String, bool>) {
+// Every column of one row, JSON text is indented and colored
+fn show_row(app: &App, view: &TableView, first_on_page: usize) -> Result<()> {
+    let index: usize = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Row number")
+        .default(first_on_page)
+        .interact()?;
+
+    let result = view.fetch(app, index, 1)?;
+    clear()?;
+
+    match result.rows.first() {
+        Some(row) => {
+            println!("Row {} of '{}'\n", index, style(app.require_active_table()?).cyan());
+
+            for (column, value) in result.columns.iter().zip(row) {
+                let pretty = match value {
+                    Value::Text(text) => json::pretty(text),
+                    _ => None,
+                };
+
+                match pretty {
+                    Some(pretty) => println!("{}:\n{}", style(&column.name).bold(), json::highlight(&app.view.sql_style, &pretty)),
+                    None => println!("{}: {}", style(&column.name).bold(), match value {
+                        Value::Blob(bytes) => blob::describe(bytes),
+                        _ => value_text(&app.config, value),
+                    }),
+                }
+            }
+        },
+        None => println!("There is no row {}", index),
+    }
+
+    println!();
+    wait_for_keypress();
+    Ok(())
+}
+
+fn set_filter(app: &App, columns: &[String], view: &mut TableView) -> Result<()> {
+    clear()?;
+
+    println!("Define condition on which rows are shown, empty shows every row");
+    if let Some(condition) = &view.condition {
+        println!("Current: WHERE {}", condition);
+    }
+    println!("Tab completes column names and keywords\n");
+
+    let condition: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("WHERE")
+        .allow_empty(true)
+        .word_completions(condition_words(columns.iter().chain(view.labels()).map(String::as_str)))
+        .interact()?;
+
+    let previous = std::mem::replace(&mut view.condition, Some(condition.trim().to_string()).filter(|condition| !condition.is_empty()));
+    if !check_view(app, view) {
+        view.condition = previous;
+    }
+
+    Ok(())
+}
+
+fn add_json_path(app: &App, candidates: &[String], view: &mut TableView) -> Result<()> {
+    clear()?;
+    println!("Read a value out of JSON text as another column, e.g. $.address.city or $.tags[0]\n");
+
+    let column = &candidates[Select::with_theme(&app.view.dialog_theme).with_prompt("Column").items(candidates).default(0).interact()?];
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("JSON path")
+        .validate_with(ValidatorAdaptor::new(|path| path.starts_with('$'), String::from("Path starts with $, e.g. $.address.city")))
+        .interact()?;
+
+    let label = format!("{}{}", column, &path[1..]);
+    view.add_column(label.clone(), db::json_extract(column, &path));
+
+    if !check_view(app, view) {
+        view.extra_columns.retain(|(existing, _)| *existing != label);
+    }
+
+    Ok(())
+}
+
+// Keys beyond these are left out, a column with free-form objects would give a grid nobody can read
+const MAX_EXPANDED_KEYS: usize = 50;
+
+// Every key of the objects in the column becomes a column
+fn expand_json(app: &App, candidates: &[String], view: &mut TableView) -> Result<()> {
+    clear()?;
+    println!("Show keys of JSON objects as columns\n");
+
+    let column = &candidates[Select::with_theme(&app.view.dialog_theme).with_prompt("Column").items(candidates).default(0).interact()?];
+    let keys = db::json_object_keys(app.require_connection()?, app.require_active_table()?, column, MAX_EXPANDED_KEYS)?;
+
+    if keys.is_empty() {
+        println!("No JSON objects are stored in '{}'\n", column);
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    for key in keys {
+        let path = if is_plain_identifier(&key) { format!("$.{}", key) } else { format!("$.\"{}\"", key) };
+        view.add_column(format!("{}.{}", column, key), db::json_extract(column, &path));
+    }
+
+    Ok(())
+}
+
+// Runs the view for one row, a mistake in a condition or a path is shown without leaving the table
+fn check_view(app: &App, view: &TableView) -> bool {
+    match view.fetch(app, 0, 1) {
+        Ok(_) => true,
+        Err(err) => {
+            print_error(&err);
+            wait_for_keypress();
+            false
+        },
+    }
+}
+
+/// Lets the user cycle each column between ascending, descending and not sorted.
+pub fn set_sorting_options(app: &App, columns: &[String], sorting_options: &mut HashMap<String, bool>) -> Result<()> {
     loop {
         let mut select = Select::with_theme(&app.view.dialog_theme);
         select.default(0);
@@ -172,8 +343,8 @@ fn set_sorting_options(app: &App, columns: &Vec<Column>, sorting_options: &mut H
             select.item(
                 format!(
                     "Column {} ({})",
-                    column.name,
-                    if let Some(ascending) = sorting_options.get(&column.name) {
+                    column,
+                    if let Some(ascending) = sorting_options.get(column) {
                         if *ascending { "ASC" } else { "DESC" }
                     } else {
                         "NONE"
@@ -183,13 +354,13 @@ fn set_sorting_options(app: &App, columns: &Vec<Column>, sorting_options: &mut H
         }
 
         select.item("Back");
-        let option = select.interact().expect("IO Error");
+        let option = select.interact()?;
 
         // exit if back
         if option >= columns.len() {
             break;
         } else {
-            let column_name = &columns.get(option).unwrap().name;
+            let column_name = columns.get(option).unwrap();
             if let Some(ascending) = sorting_options.get_mut(column_name) {
                 if *ascending {
                     *ascending = false;
@@ -201,52 +372,77 @@ fn set_sorting_options(app: &App, columns: &Vec<Column>, sorting_options: &mut H
             }
         }
     }
+
+    Ok(())
 }
 
-fn delete_rows(app: &App) {
-    clear();
+fn delete_rows(app: &App) -> Result<()> {
+    app.require_writable()?;
+    clear()?;
 
     println!("Define condition on which rows will be deleted");
-    if let Err(err) = delete_on_where(app) {
-        println!("{}", err);
-        wait_for_keypress();
-    }
+    delete_on_where(app)
 }
 
-fn delete_on_where(app: &App) -> Result<(), String> {
-    let name = app.active_table().ok_or(format!("No active table was defined"))?;
-    let connection = app.connection.as_ref().ok_or(format!("No connection is set to sqlite"))?;
+fn delete_on_where(app: &App) -> Result<()> {
+    let name = app.require_active_table()?;
+    let connection = app.require_connection()?;
+
+    println!("{}", pretty(&app.view.sql_style, &db::delete(name, None).sql));
+    println!("Tab completes column names and keywords\n");
 
-    let mut query = format!("DELETE FROM {}", name);
-    println!("{}", query);
+    let names: Vec<String> = get_table(app)?.into_iter().map(|column| column.name).collect();
+    let words = condition_words(names.iter().map(String::as_str));
 
-    // TODO: No validation. Its open to sql injection. Regex would be complicated. Its quick project so low chances.
+    // The condition is SQL and goes into the statement as written, it is not validated. Only the
+    // first statement of the text is run and the preview shows what it matches before anything changes.
     let condition: String = Input::with_theme(&app.view.dialog_theme)
         .with_prompt(
             "WHERE"
         )
-        .interact().expect("IO error");
+        .word_completions(words)
+        .interact()?;
 
-    query.push_str(" WHERE ");
-    query.push_str(condition.as_str());
+    let query = db::delete(name, Some(&condition));
 
-    if ask_for_confirmation_before_query(app, &query) {
-        connection.execute(query.as_str(), NO_PARAMS).map_err(|err| format!("{}", err))?;
+    if confirm_with_preview(app, &db::quote_identifier(name), Some(&condition), None, &query.sql)? {
+        query.execute(connection)?;
+        record_query(app, &query.sql);
     }
 
     Ok(())
 }
 
-fn value_repr(val: &Value) -> String {
+pub fn value_text(config: &Config, val: &Value) -> String {
     use rusqlite::types::Value::*;
 
-    let val = match val {
-        Null => format!("NULL"),
+    match val {
+        Null => config.null_display.clone(),
         Integer(i) => format!("{}", i),
         Real(i) => format!("{}", i),
         Text(t) => t.clone(),
         Blob(v) => format!("{:?}", v),
-    };
+    }
+}
+
+// Blobs are shown by their type and size, the bytes themselves are of no use in a grid
+pub fn value_repr(config: &Config, val: &Value) -> String {
+    match val {
+        Value::Blob(bytes) => blob::describe(bytes),
+        _ => String::from(truncate(value_text(config, val).trim_start(), config.truncate_width)),
+    }
+}
+
+// Plain names complete as they are, others only work quoted
+fn condition_words<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Vec<String> {
+    let mut words: Vec<String> = names.into_iter()
+        .map(|name| if is_plain_identifier(name) { name.to_string() } else { db::quote_identifier(name) })
+        .collect();
+    words.extend(CONDITION_KEYWORDS.iter().map(|keyword| keyword.to_string()));
+    words
+}
 
-    String::from(truncate(val.trim_start(), 20))
+fn is_plain_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
 }
@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use console::{style, Term};
+
+use rusqlite::{Connection, NO_PARAMS, types::Value};
+
+use crate::app::App;
+use crate::error::{Error, Result};
+use crate::prompt::Input;
+use crate::db::{quote_identifier, to_sqlite_literal};
+use crate::utils::{clear, wait_for_keypress};
+
+struct SchemaObject {
+    kind: String,
+    name: String,
+    sql: String,
+}
+
+pub fn dump_database(app: &App) -> Result<()> {
+    clear()?;
+    println!("Dump database into a SQL script\n");
+
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Output file")
+        .default(String::from("dump.sql"))
+        .interact()?;
+
+    dump_to_file(app.require_connection()?, &path)?;
+
+    println!("Database was dumped into '{}'\n", style(&path).green());
+    wait_for_keypress();
+    Ok(())
+}
+
+pub fn restore_from_dump(app: &App) -> Result<()> {
+    app.require_writable()?;
+    clear()?;
+    println!("Restore database from a SQL script\n");
+
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Dump file")
+        .default(String::from("dump.sql"))
+        .interact()?;
+
+    let script = std::fs::read_to_string(&path)?;
+    let connection = app.require_connection()?;
+
+    let count = restore_script(connection, &script)?;
+
+    println!("\nRestored {} statements from '{}'\n", count, style(&path).green());
+    wait_for_keypress();
+    Ok(())
+}
+
+/// Writes the dump next to `path` and renames it once it is complete, so a failed dump leaves
+/// neither a truncated script nor a half overwritten older one.
+pub fn dump_to_file(connection: &Connection, path: &str) -> Result<()> {
+    let partial = format!("{}.partial", path);
+
+    let result = File::create(&partial)
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_dump(connection, &mut out)?;
+            // Dropping the writer would flush it without reporting a full disk
+            out.flush()?;
+            Ok(())
+        })
+        .and_then(|_| std::fs::rename(&partial, path).map_err(Error::from));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Writes a `.dump` compatible script. Tables go first in foreign key order, each followed by its
+/// rows, then indexes, views and triggers in the order they were created.
+pub fn write_dump<W: Write>(connection: &Connection, out: &mut W) -> Result<()> {
+    let objects = read_schema(connection)?;
+    let shadow_tables = read_shadow_tables(connection);
+
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+
+    let tables: Vec<&SchemaObject> = objects.iter()
+        .filter(|o| o.kind == "table" && !o.name.starts_with("sqlite_") && !shadow_tables.contains(&o.name))
+        .collect();
+
+    for table in order_by_foreign_keys(connection, tables) {
+        writeln!(out, "{};", table.sql)?;
+        write_rows(connection, &table.name, out)?;
+    }
+
+    if objects.iter().any(|o| o.kind == "table" && o.name == "sqlite_sequence") {
+        writeln!(out, "DELETE FROM sqlite_sequence;")?;
+        write_rows(connection, "sqlite_sequence", out)?;
+    }
+
+    for object in objects.iter().filter(|o| o.kind != "table") {
+        writeln!(out, "{};", object.sql)?;
+    }
+
+    writeln!(out, "COMMIT;")?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Replays a script statement by statement. On failure a transaction opened by the script is rolled
+/// back and the error names the line on which the failing statement starts. Returns count of executed statements.
+pub fn restore_script(connection: &Connection, script: &str) -> Result<usize> {
+    let term = Term::stdout();
+    let line_count = script.lines().count();
+
+    let mut statement = String::new();
+    let mut statement_line = 0;
+    let mut executed = 0;
+
+    let mut lines = script.lines().enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        if statement.trim().is_empty() {
+            statement.clear();
+            statement_line = i + 1;
+        }
+
+        statement.push_str(line);
+        statement.push('\n');
+
+        let at_end = lines.peek().is_none();
+        if !at_end && !is_complete_statement(&statement) {
+            continue;
+        }
+
+        if statement.trim().is_empty() {
+            continue;
+        }
+
+        if let Err(err) = connection.execute_batch(&statement) {
+            // Without a transaction in the script earlier statements are already committed
+            let outcome = if connection.is_autocommit() {
+                format!("{} statements before it were kept", executed)
+            } else {
+                let _ = connection.execute_batch("ROLLBACK");
+                String::from("restore was rolled back")
+            };
+
+            println!();
+            return Err(Error::validation(format!(
+                "Statement on line {} failed, {}.\n{}",
+                statement_line, outcome, Error::in_statement(err, &statement)
+            )));
+        }
+
+        executed += 1;
+        statement.clear();
+
+        let _ = term.clear_line();
+        let _ = term.write_str(format!("Executed line {}/{}", i + 1, line_count).as_str());
+    }
+
+    Ok(executed)
+}
+
+fn is_complete_statement(sql: &str) -> bool {
+    use std::ffi::CString;
+
+    match CString::new(sql) {
+        Ok(sql) => unsafe { rusqlite::ffi::sqlite3_complete(sql.as_ptr()) != 0 },
+        Err(_) => false,
+    }
+}
+
+fn read_schema(connection: &Connection) -> rusqlite::Result<Vec<SchemaObject>> {
+    let mut statement = connection.prepare(
+        "SELECT type, name, sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY rowid"
+    )?;
+
+    let objects = statement.query_map(NO_PARAMS, |row| {
+        Ok(SchemaObject { kind: row.get(0)?, name: row.get(1)?, sql: row.get(2)? })
+    })?;
+
+    objects.collect()
+}
+
+// Shadow tables are created by their virtual table, dumping them would make the script fail.
+// PRAGMA table_list is not known to older sqlite versions, then there is nothing to skip.
+pub fn read_shadow_tables(connection: &Connection) -> HashSet<String> {
+    let mut shadow = HashSet::new();
+
+    if let Ok(mut statement) = connection.prepare("SELECT name FROM pragma_table_list WHERE type = 'shadow'") {
+        if let Ok(names) = statement.query_map(NO_PARAMS, |row| row.get::<usize, String>(0)) {
+            shadow.extend(names.filter_map(Result::ok));
+        }
+    }
+
+    shadow
+}
+
+fn order_by_foreign_keys<'a>(connection: &Connection, tables: Vec<&'a SchemaObject>) -> Vec<&'a SchemaObject> {
+    fn visit<'a>(
+        table: &'a SchemaObject,
+        tables: &[&'a SchemaObject],
+        connection: &Connection,
+        visited: &mut HashSet<String>,
+        ordered: &mut Vec<&'a SchemaObject>,
+    ) {
+        if !visited.insert(table.name.to_lowercase()) {
+            return;
+        }
+
+        for parent in referenced_tables(connection, &table.name) {
+            if let Some(parent) = tables.iter().find(|t| t.name.eq_ignore_ascii_case(&parent)) {
+                visit(parent, tables, connection, visited, ordered);
+            }
+        }
+
+        ordered.push(table);
+    }
+
+    let mut visited = HashSet::new();
+    let mut ordered = Vec::new();
+
+    for table in &tables {
+        visit(table, &tables, connection, &mut visited, &mut ordered);
+    }
+
+    ordered
+}
+
+fn referenced_tables(connection: &Connection, table: &str) -> Vec<String> {
+    let query = format!("SELECT DISTINCT \"table\" FROM pragma_foreign_key_list({})", to_sqlite_literal(&Value::Text(table.to_string())));
+
+    connection.prepare(&query)
+        .and_then(|mut statement| {
+            statement.query_map(NO_PARAMS, |row| row.get::<usize, String>(0))?.collect()
+        })
+        .unwrap_or_default()
+}
+
+// Generated columns can't be inserted, they are left out of the dump
+fn insertable_columns(connection: &Connection, table: &str) -> rusqlite::Result<(Vec<String>, bool)> {
+    let mut statement = connection.prepare("SELECT name, hidden FROM pragma_table_xinfo(?)")?;
+    let mut columns = Vec::new();
+    let mut skipped = false;
+
+    let mut rows = statement.query(&[table])?;
+    while let Some(row) = rows.next()? {
+        match row.get::<usize, i64>(1)? {
+            0 => columns.push(row.get(0)?),
+            _ => skipped = true,
+        }
+    }
+
+    Ok((columns, skipped))
+}
+
+fn write_rows<W: Write>(connection: &Connection, table: &str, out: &mut W) -> Result<()> {
+    let name = quote_identifier(table);
+    let (columns, skipped) = insertable_columns(connection, table)?;
+    let columns: Vec<String> = columns.iter().map(|column| quote_identifier(column)).collect();
+    let query = format!("SELECT {} FROM {}", columns.join(", "), name);
+
+    // Column list is written only when needed, so plain tables dump like the sqlite shell does
+    let target = if skipped { format!("{}({})", name, columns.join(",")) } else { name };
+
+    let mut statement = connection.prepare(query.as_str()).map_err(|err| Error::in_statement(err, &query))?;
+    let column_count = statement.column_count();
+
+    let mut rows = statement.query(NO_PARAMS)?;
+
+    while let Some(row) = rows.next()? {
+        let mut values: Vec<String> = Vec::with_capacity(column_count);
+
+        for i in 0..column_count {
+            let value: Value = row.get(i)?;
+            values.push(to_sqlite_literal(&value));
+        }
+
+        writeln!(out, "INSERT INTO {} VALUES({});", target, values.join(","))?;
+    }
+
+    Ok(())
+}
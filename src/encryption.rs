@@ -0,0 +1,172 @@
+// Encrypted databases through SQLCipher, available when built with `--features sqlcipher`.
+// Plain sqlite ignores the key pragmas, so everything here checks is_supported() first.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use console::style;
+use rusqlite::{Connection, ErrorCode, NO_PARAMS, types::Value};
+
+use crate::app::App;
+use crate::db::to_sqlite_literal;
+use crate::error::{Error, Result};
+use crate::prompt::{Input, Password, Select};
+use crate::utils::{clear, wait_for_keypress};
+
+pub const UNSUPPORTED: &str =
+    "Encrypted databases are not supported by this build. Rebuild it with `cargo build --features sqlcipher` to open them.";
+
+// Every plain sqlite file starts with this, encrypted files look like random bytes from the first one
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+pub fn is_supported() -> bool {
+    cfg!(feature = "sqlcipher")
+}
+
+/// True for an existing file which is not a plain sqlite database, missing and empty files are new databases.
+pub fn looks_encrypted(path: &str) -> bool {
+    let mut header = [0; 16];
+
+    match File::open(Path::new(path)).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Has to run before anything else reads the database.
+pub fn set_key(connection: &Connection, key: &str) -> Result<()> {
+    if !is_supported() {
+        return Err(Error::validation(UNSUPPORTED));
+    }
+
+    // Pragmas do not take parameters. The statement is not attached to errors, so the key is never printed.
+    connection.execute_batch(&format!("PRAGMA key = {}", text_literal(key)))?;
+
+    // The key is only checked once the first page is read
+    connection.query_row("SELECT COUNT(*) FROM sqlite_master", NO_PARAMS, |_| Ok(()))
+        .map_err(|err| match err {
+            rusqlite::Error::SqliteFailure(ref code, _) if code.code == ErrorCode::NotADatabase => {
+                Error::validation("Wrong key, or the file is not a database")
+            },
+            err => Error::from(err),
+        })
+}
+
+pub fn ask_for_key(app: &App, path: &str) -> Result<String> {
+    Password::with_theme(&app.view.dialog_theme)
+        .with_prompt(&format!("Key for '{}'", path))
+        .interact()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EncryptionOption {
+    Encrypt,
+    ChangeKey,
+    ExportDecrypted,
+    Back,
+}
+
+pub fn encryption_menu(app: &mut App) -> Result<()> {
+    clear()?;
+
+    if !is_supported() {
+        println!("{}\n", UNSUPPORTED);
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    if app.is_encrypted() {
+        println!("Database is {}\n", style("encrypted").green());
+    } else {
+        println!("Database is {}\n", style("not encrypted").red());
+    }
+
+    use EncryptionOption::*;
+    let options = if app.is_encrypted() { vec![ChangeKey, ExportDecrypted, Back] } else { vec![Encrypt, Back] };
+
+    let mut select = Select::with_theme(&app.view.dialog_theme);
+    select.default(0);
+    for option in &options {
+        select.item(match option {
+            Encrypt => "Encrypt into a new file",
+            ChangeKey => "Change key",
+            ExportDecrypted => "Export decrypted copy",
+            Back => "Back",
+        });
+    }
+
+    match options[select.interact()?] {
+        Encrypt => encrypt(app)?,
+        ChangeKey => change_key(app)?,
+        ExportDecrypted => export_decrypted(app)?,
+        Back => return Ok(()),
+    }
+
+    wait_for_keypress();
+    Ok(())
+}
+
+// SQLCipher cannot encrypt a file in place, the data is copied into a new database instead
+fn encrypt(app: &App) -> Result<()> {
+    let path = ask_for_new_file(app, "encrypted.db")?;
+    let key = ask_for_new_key(app)?;
+
+    export(app.require_connection()?, &path, &key)?;
+    println!("Encrypted copy written to '{}'. Open it with --key to use it.\n", path);
+    Ok(())
+}
+
+fn change_key(app: &App) -> Result<()> {
+    app.require_writable()?;
+    let key = ask_for_new_key(app)?;
+
+    app.require_connection()?.execute_batch(&format!("PRAGMA rekey = {}", text_literal(&key)))?;
+    println!("Key changed\n");
+    Ok(())
+}
+
+fn export_decrypted(app: &App) -> Result<()> {
+    let path = ask_for_new_file(app, "decrypted.db")?;
+
+    // Empty key attaches a plain database
+    export(app.require_connection()?, &path, "")?;
+    println!("Decrypted copy written to '{}'\n", path);
+    Ok(())
+}
+
+fn export(connection: &Connection, path: &str, key: &str) -> Result<()> {
+    connection.execute_batch(&format!(
+        "ATTACH DATABASE {} AS export KEY {}",
+        text_literal(path), text_literal(key)
+    ))?;
+
+    let result = connection.query_row("SELECT sqlcipher_export('export')", NO_PARAMS, |_| Ok(()));
+    connection.execute_batch("DETACH DATABASE export")?;
+
+    Ok(result?)
+}
+
+fn text_literal(text: &str) -> String {
+    to_sqlite_literal(&Value::Text(text.to_string()))
+}
+
+fn ask_for_new_file(app: &App, default: &str) -> Result<String> {
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("File for the copy")
+        .default(String::from(default))
+        .interact()?;
+
+    if Path::new(&path).exists() {
+        return Err(Error::validation(format!("File '{}' already exists", path)));
+    }
+
+    Ok(path)
+}
+
+fn ask_for_new_key(app: &App) -> Result<String> {
+    Password::with_theme(&app.view.dialog_theme)
+        .with_prompt("New key")
+        .with_confirmation("Repeat the key", "Keys do not match")
+        .interact()
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::io;
+
+use console::style;
+
+/// Every screen returns this error to the main loop, which prints it and goes back to the menu.
+#[derive(Debug)]
+pub enum Error {
+    // Statement is kept to point at the position of a syntax error
+    Sqlite { source: rusqlite::Error, statement: Option<String> },
+    Io(io::Error),
+    Validation(String),
+    // Ctrl-C or ESC inside a prompt, not an error to report
+    Cancelled,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn in_statement(source: rusqlite::Error, statement: &str) -> Self {
+        Error::Sqlite { source, statement: Some(statement.to_string()) }
+    }
+
+    pub fn validation<S: Into<String>>(cause: S) -> Self {
+        Error::Validation(cause.into())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Error::Cancelled)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(source: rusqlite::Error) -> Self {
+        Error::Sqlite { source, statement: None }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        // console reports Ctrl-C in raw mode as an interrupted read
+        if err.kind() == io::ErrorKind::Interrupted {
+            Error::Cancelled
+        } else {
+            Error::Io(err)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqlite { source: rusqlite::Error::SqliteFailure(code, message), statement } => {
+                let message = message.clone().unwrap_or_else(|| code.to_string());
+                write!(f, "SQLite error {} ({}): {}", code.extended_code, rusqlite::ffi::code_to_str(code.extended_code), message)?;
+
+                if let Some(statement) = statement {
+                    if let Some((line, column, length)) = syntax_error_position(&message, statement) {
+                        let text = statement.lines().nth(line).unwrap_or("");
+                        write!(f, "\nat line {}, column {}:\n{}\n", line + 1, column + 1, text)?;
+                        write!(f, "{}{}", " ".repeat(column), style("^".repeat(length.max(1))).red())?;
+                    }
+                }
+
+                Ok(())
+            },
+            Error::Sqlite { source, .. } => write!(f, "SQLite error: {}", source),
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Validation(cause) => write!(f, "{}", cause),
+            Error::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Sqlite reports syntax errors as `near "TOKEN": syntax error` or `incomplete input`.
+// Returns zero based line and column of the token with its length in characters. The message
+// doesn't say which occurrence failed, so a token found more than once is left to the message.
+fn syntax_error_position(message: &str, statement: &str) -> Option<(usize, usize, usize)> {
+    let token = message.strip_prefix("near \"").and_then(|rest| rest.strip_suffix("\": syntax error"));
+
+    let (offset, length) = match token {
+        Some(token) if statement.matches(token).count() == 1 => (statement.find(token)?, token.chars().count()),
+        Some(_) => return None,
+        None if message.ends_with("incomplete input") => (statement.trim_end().len(), 1),
+        None => return None,
+    };
+
+    let before = &statement[..offset];
+    let line = before.matches('\n').count();
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count();
+
+    Some((line, column, length))
+}
@@ -0,0 +1,486 @@
+use std::path::{Path, PathBuf};
+
+use console::style;
+
+use rusqlite::{Connection, ToSql, params, types::Value};
+
+use crate::app::App;
+use crate::db;
+use crate::display::draw_query;
+use crate::error::{Error, Result};
+use crate::impact::confirm_with_preview;
+use crate::prompt::{Input, Select};
+use crate::sql::{self, pretty};
+use crate::utils::{clear, wait_for_keypress, print_error, truncate, ask_for_confirmation_before_query, ValidatorAdaptor};
+use crate::value_parser::{Affinity, parse_value, validate_value};
+
+static HISTORY_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history(
+        id INTEGER PRIMARY KEY,
+        database TEXT NOT NULL,
+        query TEXT NOT NULL,
+        executed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS history_database ON history(database, id);
+    CREATE TABLE IF NOT EXISTS saved_queries(
+        database TEXT NOT NULL,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        PRIMARY KEY(database, name)
+    );
+";
+
+pub struct HistoryEntry {
+    pub query: String,
+    pub executed_at: String,
+}
+
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+}
+
+// Queries are kept in a sidecar sqlite file shared by all databases, each row is tagged with
+// the database it was run against.
+pub struct History {
+    connection: Connection,
+    database: String,
+}
+
+impl History {
+    pub fn open(path: &Path, database: &str) -> rusqlite::Result<Self> {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        let connection = Connection::open(path)?;
+        connection.execute_batch(HISTORY_SCHEMA)?;
+
+        Ok(Self { connection, database: database.to_string() })
+    }
+
+    pub fn record(&self, query: &str) -> rusqlite::Result<()> {
+        let last: Option<String> = self.connection.query_row(
+            "SELECT query FROM history WHERE database = ? ORDER BY id DESC LIMIT 1",
+            params![self.database],
+            |row| row.get(0),
+        ).ok();
+
+        if last.as_deref() == Some(query) {
+            return Ok(());
+        }
+
+        self.connection.execute(
+            "INSERT INTO history(database, query) VALUES (?, ?)",
+            params![self.database, query],
+        )?;
+
+        Ok(())
+    }
+
+    // Newest first. Empty pattern lists everything.
+    pub fn search(&self, pattern: &str, limit: usize) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let mut statement = self.connection.prepare(
+            "SELECT query, executed_at FROM history
+             WHERE database = ? AND instr(lower(query), lower(?)) > 0
+             ORDER BY id DESC LIMIT ?"
+        )?;
+
+        let entries = statement.query_map(params![self.database, pattern, limit as i64], |row| {
+            Ok(HistoryEntry { query: row.get(0)?, executed_at: row.get(1)? })
+        })?;
+
+        entries.collect()
+    }
+
+    pub fn save_query(&self, name: &str, query: &str) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO saved_queries(database, name, query) VALUES (?, ?, ?)",
+            params![self.database, name, query],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_saved_query(&self, name: &str) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "DELETE FROM saved_queries WHERE database = ? AND name = ?",
+            params![self.database, name],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn saved_queries(&self, pattern: &str) -> rusqlite::Result<Vec<SavedQuery>> {
+        let mut statement = self.connection.prepare(
+            "SELECT name, query FROM saved_queries
+             WHERE database = ?1 AND (instr(lower(name), lower(?2)) > 0 OR instr(lower(query), lower(?2)) > 0)
+             ORDER BY name"
+        )?;
+
+        let queries = statement.query_map(params![self.database, pattern], |row| {
+            Ok(SavedQuery { name: row.get(0)?, query: row.get(1)? })
+        })?;
+
+        queries.collect()
+    }
+}
+
+pub fn default_history_path() -> PathBuf {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    data_home.join("sqlite-cli-demo").join("history.db")
+}
+
+// History is a convenience, failing to write it must not interrupt the user.
+pub fn record_query(app: &App, query: &str) {
+    if let Some(history) = &app.history {
+        let _ = history.record(query);
+    }
+}
+
+/// Names of `:param` placeholders in order of their first appearance, which is also the order
+/// in which sqlite numbers them. Quoted text and comments are skipped.
+pub fn named_parameters(query: &str) -> Vec<String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut names: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            quote @ '\'' | quote @ '"' | quote @ '`' => {
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    i += 1;
+                }
+            },
+            '[' => {
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+            },
+            '-' if chars.get(i+1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            },
+            '/' if chars.get(i+1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i+1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 1;
+            },
+            ':' if matches!(chars.get(i+1), Some(c) if c.is_alphabetic() || *c == '_') => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+
+                let name: String = chars[start..i].iter().collect();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+                continue;
+            },
+            _ => {},
+        }
+
+        i += 1;
+    }
+
+    names
+}
+
+/// Prompts for placeholder values, executes the query and adds it to history. Statements which
+/// change the database are confirmed first, rows they return are drawn after they run.
+pub fn run_query(app: &App, query: &str) -> Result<()> {
+    let connection = app.require_connection()?;
+
+    let mut values: Vec<Value> = Vec::new();
+    for name in named_parameters(query) {
+        let value: String = Input::with_theme(&app.view.dialog_theme)
+            .with_prompt(format!("Value for {}", name).as_str())
+            .allow_empty(true)
+            .validate_with(ValidatorAdaptor::with_reason(|text| validate_value(text, Affinity::Blob)))
+            .interact()?;
+
+        values.push(parse_value(&value, Affinity::Blob).map_err(Error::Validation)?);
+    }
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v as &dyn ToSql).collect();
+
+    let returns_rows = connection.prepare(query)
+        .map(|statement| statement.column_count() > 0)
+        .map_err(|err| Error::in_statement(err, query))?;
+
+    if !(returns_rows && db::is_read_only(connection, query)?) {
+        // Deletes and updates without parameters can be counted before they run
+        let confirmed = match sql::target(query) {
+            Some(target) => confirm_with_preview(app, &target.table, target.condition.as_deref(), target.limit.as_deref(), query)?,
+            _ => ask_for_confirmation_before_query(app, query)?,
+        };
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    if returns_rows {
+        draw_query(app, query, &params)?;
+    } else {
+        let changed = connection.execute(query, &params).map_err(|err| Error::in_statement(err, query))?;
+        println!("{} rows changed", changed);
+    }
+
+    record_query(app, query);
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HistoryMenuOption {
+    RunQuery,
+    Browse,
+    Search,
+    SavedQueries,
+    Back,
+}
+
+impl HistoryMenuOption {
+    // Queries can change the database, read-only mode only browses and saves them
+    fn is_mutating(self) -> bool {
+        self == HistoryMenuOption::RunQuery
+    }
+}
+
+pub fn history_menu(app: &mut App) -> Result<()> {
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+
+        if app.history.is_none() {
+            println!("{}: history file could not be opened, queries are not remembered\n", style("Warning").red());
+        }
+
+        use HistoryMenuOption::*;
+        let items = [
+            (RunQuery, "Run SQL query"),
+            (Browse, "Browse history"),
+            (Search, "Search history"),
+            (SavedQueries, "Saved queries"),
+            (Back, "Back"),
+        ];
+        let items: Vec<_> = items.iter()
+            .filter(|(option, _)| !(app.is_read_only() && option.is_mutating()))
+            .collect();
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (_, text) in &items {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        match items[last_chosen].0 {
+            RunQuery => {
+                let query: String = Input::with_theme(&app.view.dialog_theme)
+                    .with_prompt("SQL")
+                    .interact()?;
+
+                run_and_wait(app, &query)?;
+            },
+            Browse => { browse_history(app, "")?; },
+            Search => {
+                let pattern: String = Input::with_theme(&app.view.dialog_theme)
+                    .with_prompt("Search for")
+                    .interact()?;
+
+                browse_history(app, &pattern)?;
+            },
+            SavedQueries => { browse_saved_queries(app)?; },
+            Back => { break; },
+        }
+    }
+
+    Ok(())
+}
+
+// A failing query is shown without leaving the console, only cancelling goes back to the menu
+fn run_and_wait(app: &App, query: &str) -> Result<()> {
+    clear()?;
+    println!("{}\n", pretty(&app.view.sql_style, query));
+
+    match run_query(app, query) {
+        Err(Error::Cancelled) => return Err(Error::Cancelled),
+        Err(err) => print_error(&err),
+        Ok(_) => println!(),
+    }
+
+    wait_for_keypress();
+    Ok(())
+}
+
+fn browse_history(app: &App, pattern: &str) -> Result<()> {
+    static SHOWN_ENTRIES: usize = 50;
+
+    let history = match &app.history {
+        Some(history) => history,
+        None => return Ok(()),
+    };
+
+    loop {
+        clear()?;
+
+        let entries = history.search(pattern, SHOWN_ENTRIES)?;
+
+        if entries.is_empty() {
+            println!("No queries found\n");
+            wait_for_keypress();
+            return Ok(());
+        }
+
+        println!("Last {} queries\n", entries.len());
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(0);
+        for entry in &entries {
+            select.item(format!("{}  {}", style(&entry.executed_at).dim(), one_line(&entry.query)).as_str());
+        }
+        select.item("Back");
+
+        let chosen = select.interact()?;
+        let entry = match entries.get(chosen) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+
+        clear()?;
+        println!("{}\n", pretty(&app.view.sql_style, &entry.query));
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(0);
+        if !app.is_read_only() {
+            select.item("Run again");
+        }
+        select.item("Save as named query");
+        select.item("Back");
+
+        // Without the run option the others move one up
+        let action = select.interact()? + usize::from(app.is_read_only());
+
+        match action {
+            0 => run_and_wait(app, &entry.query)?,
+            1 => save_query(app, history, &entry.query)?,
+            _ => {},
+        }
+    }
+}
+
+fn save_query(app: &App, history: &History, query: &str) -> Result<()> {
+    println!("Use :name placeholders in the query to be asked for values on every run\n");
+
+    let name: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Name")
+        .validate_with(ValidatorAdaptor::new(|text| !text.trim().is_empty(), String::from("Name cannot be empty")))
+        .interact()?;
+
+    let mut input = Input::with_theme(&app.view.dialog_theme);
+    input.with_prompt("Query");
+    if !query.is_empty() {
+        input.default(query.to_string());
+    }
+    let query: String = input.interact()?;
+
+    history.save_query(name.trim(), &query)?;
+    Ok(())
+}
+
+fn browse_saved_queries(app: &App) -> Result<()> {
+    let history = match &app.history {
+        Some(history) => history,
+        None => return Ok(()),
+    };
+
+    clear()?;
+    let pattern: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Filter by name or text (empty for all)")
+        .allow_empty(true)
+        .interact()?;
+
+    loop {
+        clear()?;
+
+        let queries = history.saved_queries(&pattern)?;
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(0);
+        for saved in &queries {
+            select.item(format!("{}  {}", style(&saved.name).green(), one_line(&saved.query)).as_str());
+        }
+        select.item("Save new query");
+        select.item("Back");
+
+        let chosen = select.interact()?;
+        if chosen == queries.len() {
+            clear()?;
+            save_query(app, history, "")?;
+            continue;
+        }
+
+        let saved = match queries.get(chosen) {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+
+        clear()?;
+        println!("{}\n{}\n", style(&saved.name).green(), pretty(&app.view.sql_style, &saved.query));
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(0);
+        if !app.is_read_only() {
+            select.item("Run");
+        }
+        select.item("Delete");
+        select.item("Back");
+
+        // Without the run option the others move one up
+        let action = select.interact()? + usize::from(app.is_read_only());
+
+        match action {
+            0 => run_and_wait(app, &saved.query)?,
+            1 => history.delete_saved_query(&saved.name)?,
+            _ => {},
+        }
+    }
+}
+
+fn one_line(query: &str) -> String {
+    let line = query.split_whitespace().collect::<Vec<&str>>().join(" ");
+    String::from(truncate(&line, 100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_parameters_in_order_without_repeats() {
+        assert_eq!(named_parameters("SELECT * FROM t WHERE a = :a AND b > :b_2 OR a < :a"), vec![":a", ":b_2"]);
+    }
+
+    #[test]
+    fn named_parameters_skip_quotes_and_comments() {
+        let query = "SELECT ':no', \":no\", [:no], `:no` -- :no\nFROM t /* :no */ WHERE a = :yes";
+        assert_eq!(named_parameters(query), vec![":yes"]);
+    }
+
+    #[test]
+    fn times_and_numbered_parameters_are_not_named() {
+        assert!(named_parameters("SELECT '12:30', :1 FROM t").is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+// Preview of a DELETE or UPDATE before it is confirmed: how many rows match the condition and
+// the first page of them. Large changes are confirmed by typing the row count instead of y/n.
+
+use console::style;
+use rusqlite::NO_PARAMS;
+
+use crate::app::App;
+use crate::display::draw_query;
+use crate::error::{Error, Result};
+use crate::prompt::{Confirmation, Input};
+use crate::sql::pretty;
+use crate::utils::needs_confirmation;
+
+/// `table`, `condition` and `limit` (ORDER BY and LIMIT) are SQL text as they appear in `query`,
+/// so they can be reused in SELECT statements. Returns whether the user agreed to run `query`.
+pub fn confirm_with_preview(app: &App, table: &str, condition: Option<&str>, limit: Option<&str>, query: &str) -> Result<bool> {
+    let condition = condition.map(str::trim).filter(|condition| !condition.is_empty());
+    let limit = limit.map(str::trim).filter(|limit| !limit.is_empty());
+
+    // Rows the statement changes, LIMIT makes it fewer than the condition matches
+    let mut selected = format!("SELECT * FROM {}", table);
+    if let Some(condition) = condition {
+        selected.push_str(&format!(" WHERE {}", condition));
+    }
+    if let Some(limit) = limit {
+        selected.push_str(&format!(" {}", limit));
+    }
+
+    println!("You are about to execute following query:");
+    println!("{}\n", pretty(&app.view.sql_style, query));
+
+    let total = count(app, &format!("SELECT COUNT(*) FROM {}", table))?;
+    let affected = count(app, &format!("SELECT COUNT(*) FROM ({})", selected))?;
+
+    if condition.is_none() && limit.is_none() {
+        println!("{}: there is no condition, every row of the table is affected\n", style("Warning").red().bold());
+    } else if affected == total && total > 0 {
+        println!("{}: condition matches every row of the table\n", style("Warning").red().bold());
+    }
+
+    if affected == 0 {
+        println!("No rows match the condition, nothing would change\n");
+        return Ok(false);
+    }
+
+    let shown = affected.min(app.config.page_size);
+    if shown < affected {
+        println!("{} of {} rows are affected, first {} of them:", style(affected).red(), total, shown);
+    } else {
+        println!("{} of {} rows are affected:", style(affected).red(), total);
+    }
+    draw_query(app, &format!("SELECT * FROM ({}) LIMIT {}", selected, shown), &[])?;
+    println!();
+
+    if affected > app.config.typed_confirmation_rows {
+        let typed: String = Input::with_theme(&app.view.dialog_theme)
+            .with_prompt(format!("Type {} to proceed", affected).as_str())
+            .allow_empty(true)
+            .interact()?;
+
+        if typed.trim() != affected.to_string() {
+            println!("Row count does not match, nothing was changed\n");
+            return Ok(false);
+        }
+
+        return Ok(true);
+    }
+
+    if !needs_confirmation(app, query) {
+        return Ok(true);
+    }
+
+    Confirmation::with_theme(&app.view.dialog_theme).with_text("Do you proceed?").interact()
+}
+
+fn count(app: &App, sql: &str) -> Result<usize> {
+    let count: i64 = app.require_connection()?
+        .query_row(sql, NO_PARAMS, |row| row.get(0))
+        .map_err(|err| Error::in_statement(err, sql))?;
+
+    Ok(count as usize)
+}
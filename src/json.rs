@@ -0,0 +1,136 @@
+// Pretty printing of JSON text stored in columns. Validity is left to sqlite's json_valid(), the
+// tokenizer only has to find strings, brackets and separators to break lines and pick colors.
+
+use crate::sql::SqlStyle;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TokenKind {
+    Open,
+    Close,
+    Comma,
+    Colon,
+    String,
+    // Numbers, true, false and null
+    Scalar,
+    Whitespace,
+}
+
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+}
+
+// None for unterminated strings and characters which can't appear in JSON
+fn tokenize(text: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            '{' | '[' => TokenKind::Open,
+            '}' | ']' => TokenKind::Close,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '"' => {
+                let mut escaped = false;
+                loop {
+                    let (_, c) = chars.next()?;
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+                TokenKind::String
+            },
+            c if c.is_whitespace() => {
+                while chars.peek().is_some_and(|(_, c)| c.is_whitespace()) { chars.next(); }
+                TokenKind::Whitespace
+            },
+            c if c.is_ascii_alphanumeric() || c == '-' => {
+                while chars.peek().is_some_and(|(_, c)| c.is_ascii_alphanumeric() || "+-.".contains(*c)) { chars.next(); }
+                TokenKind::Scalar
+            },
+            _ => return None,
+        };
+
+        let end = chars.peek().map_or(text.len(), |(end, _)| *end);
+        tokens.push(Token { kind, text: &text[start..end] });
+    }
+
+    Some(tokens)
+}
+
+/// Indents objects and arrays by two spaces per level. None for text which is not an object or
+/// an array, or whose brackets don't match.
+pub fn pretty(text: &str) -> Option<String> {
+    if !text.trim_start().starts_with(['{', '[']) {
+        return None;
+    }
+
+    let tokens: Vec<Token> = tokenize(text)?.into_iter().filter(|token| token.kind != TokenKind::Whitespace).collect();
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut open: Vec<&str> = Vec::new();
+
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Open => {
+                out.push_str(token.text);
+                open.push(token.text);
+                // Empty objects and arrays stay on one line
+                if tokens.get(i + 1).is_some_and(|next| next.kind != TokenKind::Close) {
+                    newline(&mut out, open.len());
+                }
+            },
+            TokenKind::Close => {
+                let matching = if token.text == "}" { "{" } else { "[" };
+                if open.pop()? != matching {
+                    return None;
+                }
+                if tokens[i - 1].kind != TokenKind::Open {
+                    newline(&mut out, open.len());
+                }
+                out.push_str(token.text);
+            },
+            TokenKind::Comma => {
+                out.push(',');
+                newline(&mut out, open.len());
+            },
+            TokenKind::Colon => out.push_str(": "),
+            _ => out.push_str(token.text),
+        }
+
+        if open.is_empty() && i + 1 < tokens.len() {
+            return None;
+        }
+    }
+
+    if open.is_empty() { Some(out) } else { None }
+}
+
+/// Colors keys like identifiers, values like literals and true, false and null like keywords.
+pub fn highlight(style: &SqlStyle, text: &str) -> String {
+    let tokens = match tokenize(text) {
+        Some(tokens) if console::colors_enabled() => tokens,
+        _ => return text.to_string(),
+    };
+
+    tokens.iter().enumerate()
+        .map(|(i, token)| {
+            let is_key = || tokens[i + 1..].iter().find(|next| next.kind != TokenKind::Whitespace).is_some_and(|next| next.kind == TokenKind::Colon);
+
+            match token.kind {
+                TokenKind::String if is_key() => style.identifier.apply_to(token.text).to_string(),
+                TokenKind::String => style.literal.apply_to(token.text).to_string(),
+                TokenKind::Scalar if matches!(token.text, "true" | "false" | "null") => style.keyword.apply_to(token.text).to_string(),
+                TokenKind::Scalar => style.literal.apply_to(token.text).to_string(),
+                _ => token.text.to_string(),
+            }
+        })
+        .collect()
+}
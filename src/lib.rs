@@ -6,19 +6,47 @@ extern crate console;
 extern crate num_traits;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
+extern crate serde;
+extern crate toml;
+extern crate crossterm;
+extern crate ratatui;
+extern crate clap;
+#[cfg(unix)] extern crate libc;
 
-use dialoguer::{Select, Input};
-use num_traits::FromPrimitive;
-use console::style;
 
 pub mod define_table;
 pub mod utils;
 pub mod app;
 pub mod insert_row;
 pub mod display;
+pub mod dump;
+pub mod value_parser;
+pub mod data_quality;
+pub mod config;
+pub mod history;
+pub mod db;
+pub mod error;
+pub mod prompt;
+pub mod tui;
+pub mod encryption;
+pub mod maintenance;
+pub mod storage;
+pub mod schema_diff;
+pub mod data_diff;
+pub mod migrations;
+pub mod commands;
+pub mod cli;
+pub mod sql;
+pub mod impact;
+pub mod blob;
+pub mod json;
+pub mod search;
+pub mod summary;
 
 use crate::app::App;
-use crate::utils::{ValidatorAdaptor, validate_table_name, wait_for_keypress, clear};
+use crate::error::Result;
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, did_you_mean, ValidatorAdaptor};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive)]
 pub enum MainMenuOption {
@@ -26,43 +54,92 @@ pub enum MainMenuOption {
     SelectTable = 1,
     InsertRow = 2,
     Display = 3,
-    Quit = 4,
+    DataQuality = 4,
+    DumpDatabase = 5,
+    RestoreDump = 6,
+    History = 7,
+    SchemaDiff = 8,
+    DataDiff = 9,
+    Storage = 10,
+    Encryption = 11,
+    Maintenance = 12,
+    Migrations = 13,
+    Settings = 14,
+    Quit = 15,
 }
 
+impl MainMenuOption {
+    pub fn is_mutating(self) -> bool {
+        use MainMenuOption::*;
+        matches!(self, DefineTable | InsertRow | RestoreDump | Encryption)
+    }
+}
+
+
+pub fn ask_main_menu(app: &App) -> Result<MainMenuOption> {
+    use MainMenuOption::*;
+
+    let read_only = app.is_read_only();
+    let items = [
+        (DefineTable, "Define new table"),
+        (SelectTable, "Select existing table"),
+        (InsertRow, "Insert row"),
+        (Display, if read_only { "Display rows" } else { "Display or remove rows" }),
+        (DataQuality, "Data quality report"),
+        (DumpDatabase, "Dump database"),
+        (RestoreDump, "Restore from dump"),
+        (History, "SQL query and history"),
+        (SchemaDiff, "Compare schema with another database"),
+        (DataDiff, "Compare rows with another table"),
+        (Storage, "Storage report"),
+        (Encryption, "Encryption"),
+        (Maintenance, "Maintenance"),
+        (Migrations, "Migrations"),
+        (Settings, "Settings"),
+        (Quit, "Quit"),
+    ];
 
-pub fn ask_main_menu(app: &App) -> Result<MainMenuOption, std::io::Error>{
-    let option = Select::with_theme(&app.view.dialog_theme)
-        .default(0)
-        .item("Define new table")
-        .item("Select existing table")
-        .item("Insert row")
-        .item("Display or remove rows")
-        .item("Quit")
-        .interact();
+    // Options which only change the database are left out in read-only mode
+    let items: Vec<(MainMenuOption, &str)> = items.iter()
+        .filter(|(option, _)| !(read_only && option.is_mutating()))
+        .cloned()
+        .collect();
 
-    match option {
-        Ok(opt) => Ok(MainMenuOption::from_usize(opt).unwrap()),
-        Err(err) => Err(err),
+    let mut select = Select::with_theme(&app.view.dialog_theme);
+    select.default(0);
+    for (_, text) in &items {
+        select.item(text);
     }
+
+    let option = select.interact()?;
+    Ok(items.get(option).expect("menu option not in vector").0)
 }
 
-pub fn set_active_table(app: &mut App) {
-    clear();
-    println!("Give table name\n");
+pub fn set_active_table(app: &mut App) -> Result<()> {
+    clear()?;
+    println!("Give table name, Tab completes it\n");
 
-    let name: String = Input::with_theme(&app.view.dialog_theme)
-        .with_prompt("name")
-        .validate_with(ValidatorAdaptor::new(validate_table_name, format!("Table name must be alphanumeric")))
-        .interact().expect("IO error");
+    let name = ask_for_table(app)?;
+    app.set_active_table(name.as_str())
+}
 
-    match app.set_active_table(name.as_str()) {
-        Ok(_) => {},
-        Err(err) => {
-            println!("Could not set \'{}\' table", style(name).cyan());
-            println!("Reason: {}\n", err);
+/// Asks for the name of an existing table, misspelled names are asked again with suggestions.
+pub fn ask_for_table(app: &App) -> Result<String> {
+    let names = db::table_names(app.require_connection()?)?;
+    let known = names.clone();
 
-            wait_for_keypress();
-        }
-    }
+    let text: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("name")
+        .completions(names.clone())
+        .validate_with(ValidatorAdaptor::with_reason(move |text| {
+            if known.iter().any(|name| name.eq_ignore_ascii_case(text)) {
+                Ok(())
+            } else {
+                Err(format!("Table '{}' does not exist.{}", text, did_you_mean(text, known.iter().map(String::as_str))))
+            }
+        }))
+        .interact()?;
 
+    // sqlite ignores case of ASCII letters in names, the name is kept as the schema spells it
+    Ok(names.into_iter().find(|name| name.eq_ignore_ascii_case(&text)).unwrap_or(text))
 }
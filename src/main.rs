@@ -8,15 +8,72 @@ use sqlite::utils::*;
 use sqlite::define_table::*;
 use sqlite::insert_row::*;
 use sqlite::display::display_table;
+use sqlite::dump::*;
+use sqlite::data_quality::data_quality;
+use sqlite::config::*;
+use sqlite::history::*;
+use sqlite::encryption::{self, encryption_menu};
+use sqlite::error::Error;
+use sqlite::maintenance::maintenance;
+use sqlite::storage::storage_report;
+use sqlite::schema_diff::{self, schema_diff};
+use sqlite::data_diff::data_diff;
+use sqlite::migrations::migrations_menu;
+use sqlite::cli;
 
 
 fn main() {
-    let matches = get_matches();
+    let matches = cli::build().get_matches();
 
-    let mut app = App::new();
+    match cli::run_standalone(&matches) {
+        Ok(true) => return,
+        Ok(false) => {},
+        Err(err) => {
+            eprintln!("{}: {}", style("Error").red(), err);
+            std::process::exit(1);
+        },
+    }
+
+    let config_path = match matches.value_of("config") {
+        Some(path) => std::path::PathBuf::from(path),
+        None => default_config_path(),
+    };
+
+    let config = match Config::load(&config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Could not load settings from '{}'. {}", config_path.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut app = App::with_config(config, config_path);
 
     if let Some(path) = matches.value_of("path") {
-        if let Err(err) = app.connect_in_file(path) {
+        let mode = OpenMode {
+            read_only: matches.is_present("readonly"),
+            no_create: matches.is_present("no-create"),
+            immutable: matches.is_present("immutable"),
+        };
+
+        if matches.is_present("key") && !encryption::is_supported() {
+            println!("{}", encryption::UNSUPPORTED);
+            std::process::exit(1);
+        }
+
+        // Encrypted files ask for the key when it was not given
+        let key = match matches.value_of("key") {
+            Some(key) => Some(key.to_string()),
+            None if matches.is_present("key") || (encryption::is_supported() && encryption::looks_encrypted(path)) => {
+                match encryption::ask_for_key(&app, path) {
+                    Ok(key) => Some(key),
+                    Err(_) => std::process::exit(1),
+                }
+            },
+            None => None,
+        };
+
+        if let Err(err) = app.connect_in_file(path, mode, key.as_deref()) {
             println!("Cannot connect to sqlite. Error: {}", err);
             std::process::exit(1);
         }
@@ -27,13 +84,89 @@ fn main() {
         }
     }
 
+    if matches.is_present("dump") {
+        let result = app.require_connection().and_then(|connection| match matches.value_of("dump") {
+            Some(file) => dump_to_file(connection, file),
+            None => write_dump(connection, &mut std::io::stdout().lock()),
+        });
+
+        if let Err(err) = result {
+            eprintln!("Could not dump database. {}", err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(other_path) = matches.value_of("diff") {
+        let result = schema_diff::open_other(other_path)
+            .and_then(|other| schema_diff::SchemaDiff::compare(app.require_connection()?, &other))
+            .and_then(|diff| if matches.is_present("migration") {
+                diff.write_migration(&mut std::io::stdout().lock())
+            } else {
+                diff.print_report();
+                Ok(())
+            });
+
+        if let Err(err) = result {
+            eprintln!("Could not compare schemas. {}", err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let (name, Some(sub_matches)) = matches.subcommand() {
+        if cli::changes_database(name) && app.is_in_memory() {
+            eprintln!("{}: '{}' changes the database, give its file with --path", style("Error").red(), name);
+            std::process::exit(1);
+        }
+
+        match cli::run_subcommand(&app, name, sub_matches) {
+            Ok(_) | Err(Error::Cancelled) => {},
+            Err(err) => {
+                eprintln!("{}: {}", style("Error").red(), err);
+                std::process::exit(1);
+            },
+        }
+
+        return;
+    }
+
     let (in_memory, path_text) = get_sqlite_path(&app);
 
+    app.history = match History::open(&default_history_path(), path_text.as_str()) {
+        Ok(history) => Some(history),
+        Err(err) => {
+            println!("Could not open query history. Error: {}", err);
+            wait_for_keypress();
+            None
+        }
+    };
+
+    if matches.is_present("tui") {
+        if let Err(err) = sqlite::tui::run(&mut app, path_text.as_str()) {
+            if !err.is_cancelled() {
+                print_error(&err);
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
     loop {
-        clear();
+        if let Err(err) = clear() {
+            println!("Cannot use the terminal. {}", err);
+            std::process::exit(1);
+        }
 
         println!("Welcome to sqlite interactive demo.");
-        println!("Sqlite is running in {}\n", color_sqlite_path(in_memory, path_text.as_str()));
+        match app.database_size() {
+            Ok(size) => println!("Sqlite is running in {} ({})", color_sqlite_path(in_memory, path_text.as_str()), format_size(size)),
+            Err(_) => println!("Sqlite is running in {}", color_sqlite_path(in_memory, path_text.as_str())),
+        }
+        println!("Mode: {}\n", mode_description(&app));
 
         if let Some(table) = app.active_table() {
             println!("Current table: \'{}\'", style(table).green());
@@ -42,30 +175,38 @@ fn main() {
         }
 
         use sqlite::MainMenuOption::*;
-        match ask_main_menu(&app).expect("IO error") {
-            DefineTable => { define_table(&mut app); },
-            SelectTable => { set_active_table(&mut app); },
-            InsertRow => { insert_row(&mut app); },
-            Display => { display_table(&app); },
-            Quit => { break; },
+        let result = match ask_main_menu(&app) {
+            Ok(DefineTable) => define_table(&mut app),
+            Ok(SelectTable) => set_active_table(&mut app),
+            Ok(InsertRow) => insert_row(&mut app),
+            Ok(Display) => display_table(&app),
+            Ok(DataQuality) => data_quality(&mut app),
+            Ok(DumpDatabase) => dump_database(&app),
+            Ok(RestoreDump) => restore_from_dump(&app),
+            Ok(History) => history_menu(&mut app),
+            Ok(SchemaDiff) => schema_diff(&app),
+            Ok(DataDiff) => data_diff(&app),
+            Ok(Storage) => storage_report(&app),
+            Ok(Encryption) => encryption_menu(&mut app),
+            Ok(Maintenance) => maintenance(&mut app),
+            Ok(Migrations) => migrations_menu(&app),
+            Ok(Settings) => settings(&mut app),
+            Ok(Quit) => { break; },
+            Err(err) => Err(err),
+        };
+
+        // Cancelling a prompt only brings the menu back
+        match result {
+            Ok(_) | Err(Error::Cancelled) => {},
+            Err(err) => {
+                println!();
+                print_error(&err);
+                wait_for_keypress();
+            },
         }
     }
 }
 
-fn get_matches<'a>() -> clap::ArgMatches<'a> {
-    use clap::*;
-    App::new("Sqlite demonstration")
-        .version("0.1.0")
-        .author("Karol Milewczyk <kmilewczyk96@gmail.com>")
-        .about("Implementation of uni assignment. It is interactive demonstration of sqlite basic features")
-        .arg(Arg::with_name("path")
-            .short("p")
-            .long("path")
-            .takes_value(true)
-            .help("Forces sqlite to work on a file in the specified path"))
-        .get_matches()
-}
-
 fn get_sqlite_path(app: &App) -> (bool, String) {
     if let Some(path) = app.path() {
         let abs_path = match std::fs::canonicalize(path) {
@@ -89,3 +230,20 @@ fn color_sqlite_path(in_memory: bool, text: &str) -> console::StyledObject<&str>
         false => style(text).green(),
     }
 }
+
+fn mode_description(app: &App) -> String {
+    let mode = app.open_mode();
+    let access = if mode.immutable {
+        style("immutable, read-only").red().bold()
+    } else if mode.read_only {
+        style("read-only").red().bold()
+    } else {
+        style("read-write").green()
+    };
+
+    if app.is_encrypted() {
+        format!("{}, {}", access, style("encrypted").cyan())
+    } else {
+        access.to_string()
+    }
+}
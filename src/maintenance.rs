@@ -0,0 +1,161 @@
+use console::style;
+
+use crate::app::App;
+use crate::db::{self, JournalMode};
+use crate::display::draw_query;
+use crate::error::{Error, Result};
+use crate::history::record_query;
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, format_size, wait_for_keypress, ask_for_confirmation_before_query};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MaintenanceOption {
+    IntegrityCheck,
+    QuickCheck,
+    ForeignKeyCheck,
+    Vacuum,
+    VacuumInto,
+    Analyze,
+    Optimize,
+    JournalMode,
+    Back,
+}
+
+impl MaintenanceOption {
+    fn is_mutating(self) -> bool {
+        use MaintenanceOption::*;
+        matches!(self, Vacuum | Analyze | Optimize | JournalMode)
+    }
+}
+
+pub fn maintenance(app: &mut App) -> Result<()> {
+    use MaintenanceOption::*;
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+        println!("Database maintenance\n");
+        print_storage_info(app)?;
+
+        let items = [
+            (IntegrityCheck, "Integrity check"),
+            (QuickCheck, "Quick check"),
+            (ForeignKeyCheck, "Foreign key check"),
+            (Vacuum, "VACUUM"),
+            (VacuumInto, "VACUUM INTO a new file"),
+            (Analyze, "ANALYZE"),
+            (Optimize, "PRAGMA optimize"),
+            (JournalMode, "Switch journal mode"),
+            (Back, "Back"),
+        ];
+        let items: Vec<_> = items.iter()
+            .filter(|(option, _)| !(app.is_read_only() && option.is_mutating()))
+            .collect();
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (_, text) in &items {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        println!();
+        match items[last_chosen].0 {
+            IntegrityCheck => draw_query(app, "PRAGMA integrity_check", &[])?,
+            QuickCheck => draw_query(app, "PRAGMA quick_check", &[])?,
+            ForeignKeyCheck => foreign_key_check(app)?,
+            Vacuum => vacuum(app)?,
+            VacuumInto => vacuum_into(app)?,
+            Analyze => run_statement(app, "ANALYZE")?,
+            Optimize => run_statement(app, "PRAGMA optimize")?,
+            JournalMode => switch_journal_mode(app)?,
+            Back => return Ok(()),
+        }
+
+        println!();
+        wait_for_keypress();
+    }
+}
+
+fn print_storage_info(app: &App) -> Result<()> {
+    let info = db::storage_info(app.require_connection()?)?;
+
+    println!("Page size:      {}", info.page_size);
+    println!("Page count:     {}", info.page_count);
+    println!("Freelist count: {}", info.freelist_count);
+    println!("Journal mode:   {}", style(&info.journal_mode).cyan());
+    println!("Size:           {}\n", format_size(app.database_size()?));
+
+    Ok(())
+}
+
+fn foreign_key_check(app: &App) -> Result<()> {
+    let result = db::fetch(app.require_connection()?, "PRAGMA foreign_key_check", &[])?;
+
+    if result.rows.is_empty() {
+        println!("No foreign key violations");
+        Ok(())
+    } else {
+        crate::display::draw_result(&app.config, &result)
+    }
+}
+
+fn vacuum(app: &App) -> Result<()> {
+    let before = app.database_size()?;
+    run_statement(app, "VACUUM")?;
+    let after = app.database_size()?;
+
+    println!("Size before: {}, after: {} ({} reclaimed)", format_size(before), format_size(after), format_size(before.saturating_sub(after)));
+    Ok(())
+}
+
+fn vacuum_into(app: &App) -> Result<()> {
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("File for the compacted copy")
+        .default(String::from("vacuumed.db"))
+        .interact()?;
+
+    // VACUUM INTO refuses to overwrite anything but an empty file, check first for a clearer message
+    if std::path::Path::new(&path).exists() {
+        return Err(Error::validation(format!("File '{}' already exists", path)));
+    }
+
+    db::vacuum_into(app.require_connection()?, &path)?;
+
+    let after = std::fs::metadata(&path)?.len();
+    println!("Size of the database: {}, of the copy in '{}': {}", format_size(app.database_size()?), path, format_size(after));
+    Ok(())
+}
+
+fn run_statement(app: &App, sql: &str) -> Result<()> {
+    if !ask_for_confirmation_before_query(app, sql)? {
+        return Ok(());
+    }
+
+    app.require_connection()?.execute_batch(sql).map_err(|err| Error::in_statement(err, sql))?;
+    record_query(app, sql);
+    println!("Done");
+    Ok(())
+}
+
+fn switch_journal_mode(app: &App) -> Result<()> {
+    let modes = [JournalMode::Wal, JournalMode::Delete];
+
+    let option = Select::with_theme(&app.view.dialog_theme)
+        .with_prompt("Journal mode")
+        .default(0)
+        .item("WAL, readers do not block the writer")
+        .item("DELETE, rollback journal removed after each transaction")
+        .interact()?;
+
+    let requested = modes[option];
+    let mode = db::set_journal_mode(app.require_connection()?, requested)?;
+
+    if mode.eq_ignore_ascii_case(requested.name()) {
+        println!("Journal mode is now {}", style(&mode).cyan());
+    } else {
+        println!("Journal mode could not be changed and stays {}", style(&mode).red());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,368 @@
+// Versioned migrations read from a directory of `NNNN_name.up.sql` and `NNNN_name.down.sql` files.
+// Applied versions are kept in the `_migrations` table. Every file runs in its own transaction
+// together with the statement which records it, so files must not contain BEGIN or COMMIT.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use console::style;
+use regex::Regex;
+use rusqlite::{Connection, NO_PARAMS, params, types::Value};
+
+use crate::app::App;
+use crate::db::{ResultColumn, ResultSet};
+use crate::display::draw_result;
+use crate::error::{Error, Result};
+use crate::history::record_query;
+use crate::prompt::{Input, Select};
+use crate::sql::highlight;
+use crate::utils::{clear, wait_for_keypress, ask_for_confirmation_before_query};
+
+pub const DEFAULT_DIRECTORY: &str = "migrations";
+
+const TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS _migrations (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+)";
+
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub up: PathBuf,
+    pub down: Option<PathBuf>,
+}
+
+impl Migration {
+    fn label(&self) -> String {
+        format!("{:04}_{}", self.version, self.name)
+    }
+
+    fn file(&self, direction: Direction) -> Result<&Path> {
+        match direction {
+            Direction::Up => Ok(&self.up),
+            Direction::Down => self.down.as_deref()
+                .ok_or_else(|| Error::validation(format!("Migration {} has no .down.sql file", self.label()))),
+        }
+    }
+}
+
+pub struct AppliedMigration {
+    pub name: String,
+    pub applied_at: String,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+impl Direction {
+    fn done(self) -> &'static str {
+        match self {
+            Direction::Up => "Applied",
+            Direction::Down => "Reverted",
+        }
+    }
+
+    fn nothing_to_do(self) -> &'static str {
+        match self {
+            Direction::Up => "No pending migrations",
+            Direction::Down => "No applied migrations",
+        }
+    }
+}
+
+/// `migrate` subcommand of the command line.
+pub struct Command {
+    pub directory: String,
+    pub action: Action,
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    Status,
+    // Count of migrations, all pending ones when not given
+    Up(Option<usize>),
+    // Count of migrations, the last applied one when not given
+    Down(Option<usize>),
+}
+
+/// Migrations found in the directory ordered by version. Files not named like a migration are skipped.
+pub fn read_migrations(directory: &Path) -> Result<Vec<Migration>> {
+    lazy_static! {
+        static ref FILE_REGEX: Regex = Regex::new(r"^(\d+)_(.+)\.(up|down)\.sql$").unwrap();
+    }
+
+    let mut ups: BTreeMap<i64, (String, PathBuf)> = BTreeMap::new();
+    let mut downs: BTreeMap<i64, PathBuf> = BTreeMap::new();
+
+    let mut entries = std::fs::read_dir(directory)
+        .map_err(|err| Error::validation(format!("Cannot read migrations from '{}'. {}", directory.display(), err)))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let captures = match FILE_REGEX.captures(&file_name) {
+            Some(captures) => captures,
+            None => continue,
+        };
+
+        let version: i64 = captures[1].parse()
+            .map_err(|_| Error::validation(format!("Version of '{}' is too large", file_name)))?;
+        let previous = if &captures[3] == "up" {
+            ups.insert(version, (captures[2].to_string(), entry.path())).map(|(_, path)| path)
+        } else {
+            downs.insert(version, entry.path())
+        };
+
+        if let Some(previous) = previous {
+            return Err(Error::validation(format!(
+                "Files '{}' and '{}' share version {}", previous.display(), entry.path().display(), version
+            )));
+        }
+    }
+
+    if let Some((_, path)) = downs.iter().find(|(version, _)| !ups.contains_key(version)) {
+        return Err(Error::validation(format!("'{}' has no matching .up.sql file", path.display())));
+    }
+
+    Ok(ups.into_iter()
+        .map(|(version, (name, up))| Migration { version, name, up, down: downs.remove(&version) })
+        .collect())
+}
+
+/// Versions recorded in `_migrations`, a database without the table has nothing applied.
+pub fn applied_migrations(connection: &Connection) -> Result<BTreeMap<i64, AppliedMigration>> {
+    let exists: bool = connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_migrations')",
+        NO_PARAMS, |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut statement = connection.prepare("SELECT version, name, applied_at FROM _migrations")?;
+    let rows = statement.query_map(NO_PARAMS, |row| {
+        Ok((row.get(0)?, AppliedMigration { name: row.get(1)?, applied_at: row.get(2)? }))
+    })?;
+
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Pending migrations in the order they are applied.
+pub fn plan_up<'a>(migrations: &'a [Migration], applied: &BTreeMap<i64, AppliedMigration>, count: Option<usize>) -> Vec<&'a Migration> {
+    migrations.iter()
+        .filter(|migration| !applied.contains_key(&migration.version))
+        .take(count.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Applied migrations in the order they are reverted, newest first.
+pub fn plan_down<'a>(migrations: &'a [Migration], applied: &BTreeMap<i64, AppliedMigration>, count: Option<usize>) -> Result<Vec<&'a Migration>> {
+    applied.iter().rev()
+        .take(count.unwrap_or(1))
+        .map(|(version, record)| {
+            let migration = migrations.iter().find(|migration| migration.version == *version)
+                .ok_or_else(|| Error::validation(format!(
+                    "Migration {:04}_{} is applied, but its files are missing", version, record.name
+                )))?;
+
+            migration.file(Direction::Down)?;
+            Ok(migration)
+        })
+        .collect()
+}
+
+/// Runs the file and records the change in one transaction, nothing is left behind when it fails.
+pub fn apply(connection: &Connection, migration: &Migration, direction: Direction, sql: &str) -> Result<()> {
+    connection.execute_batch("BEGIN")?;
+
+    let result = connection.execute_batch(TABLE_SQL).map_err(Error::from)
+        .and_then(|_| connection.execute_batch(sql).map_err(|err| Error::in_statement(err, sql)))
+        .and_then(|_| match direction {
+            Direction::Up => connection.execute(
+                "INSERT INTO _migrations (version, name) VALUES (?, ?)", params![migration.version, migration.name]
+            ),
+            Direction::Down => connection.execute("DELETE FROM _migrations WHERE version = ?", params![migration.version]),
+        }.map_err(Error::from))
+        .and_then(|_| connection.execute_batch("COMMIT").map_err(Error::from));
+
+    if let Err(err) = result {
+        if !connection.is_autocommit() {
+            let _ = connection.execute_batch("ROLLBACK");
+        }
+
+        return Err(Error::validation(format!("Migration {} was rolled back.\n{}", migration.label(), err)));
+    }
+
+    Ok(())
+}
+
+fn read_sql(migration: &Migration, direction: Direction) -> Result<String> {
+    Ok(std::fs::read_to_string(migration.file(direction)?)?)
+}
+
+pub fn status(migrations: &[Migration], applied: &BTreeMap<i64, AppliedMigration>) -> ResultSet {
+    let mut rows: BTreeMap<i64, Vec<Value>> = BTreeMap::new();
+
+    for migration in migrations {
+        let (state, applied_at) = match applied.get(&migration.version) {
+            Some(record) => ("applied", Value::Text(record.applied_at.clone())),
+            None => ("pending", Value::Null),
+        };
+        rows.insert(migration.version, vec![
+            Value::Integer(migration.version), Value::Text(migration.name.clone()), Value::Text(state.to_string()), applied_at,
+        ]);
+    }
+
+    for (version, record) in applied.iter().filter(|(version, _)| !migrations.iter().any(|m| m.version == **version)) {
+        rows.insert(*version, vec![
+            Value::Integer(*version), Value::Text(record.name.clone()),
+            Value::Text("applied, file missing".to_string()), Value::Text(record.applied_at.clone()),
+        ]);
+    }
+
+    ResultSet {
+        columns: ["version", "name", "state", "applied_at"].iter()
+            .map(|name| ResultColumn { name: name.to_string(), decl_type: None })
+            .collect(),
+        rows: rows.into_values().collect(),
+    }
+}
+
+/// Runs the `migrate` subcommand, prints only what was done.
+pub fn run_command(app: &App, command: &Command) -> Result<()> {
+    let migrations = read_migrations(Path::new(&command.directory))?;
+    let connection = app.require_connection()?;
+    let applied = applied_migrations(connection)?;
+
+    let (direction, planned) = match command.action {
+        Action::Status => return draw_result(&app.config, &status(&migrations, &applied)),
+        Action::Up(count) => (Direction::Up, plan_up(&migrations, &applied, count)),
+        Action::Down(count) => (Direction::Down, plan_down(&migrations, &applied, count)?),
+    };
+
+    if planned.is_empty() {
+        println!("{}", direction.nothing_to_do());
+        return Ok(());
+    }
+
+    if !command.dry_run {
+        app.require_writable()?;
+    }
+
+    for migration in planned {
+        let sql = read_sql(migration, direction)?;
+
+        if command.dry_run {
+            println!("-- {}", migration.file(direction)?.display());
+            println!("{}\n", highlight(&app.view.sql_style, sql.trim_end()));
+        } else {
+            apply(connection, migration, direction, &sql)?;
+            record_query(app, &sql);
+            println!("{} {}", direction.done(), migration.label());
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MigrationsOption {
+    ApplyPending,
+    ApplyNext,
+    RevertLast,
+    DryRun,
+    Back,
+}
+
+impl MigrationsOption {
+    fn is_mutating(self) -> bool {
+        use MigrationsOption::*;
+        matches!(self, ApplyPending | ApplyNext | RevertLast)
+    }
+}
+
+pub fn migrations_menu(app: &App) -> Result<()> {
+    use MigrationsOption::*;
+
+    clear()?;
+    println!("Versioned migrations\n");
+
+    let directory: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Directory with migrations")
+        .default(String::from(DEFAULT_DIRECTORY))
+        .interact()?;
+
+    let mut last_chosen = 0;
+    loop {
+        let migrations = read_migrations(Path::new(&directory))?;
+        let applied = applied_migrations(app.require_connection()?)?;
+
+        clear()?;
+        println!("Migrations in '{}'\n", style(&directory).green());
+        draw_result(&app.config, &status(&migrations, &applied))?;
+        println!();
+
+        let items = [
+            (ApplyPending, "Apply pending migrations"),
+            (ApplyNext, "Apply next migration"),
+            (RevertLast, "Revert last migration"),
+            (DryRun, "Show statements of pending migrations"),
+            (Back, "Back"),
+        ];
+        let items: Vec<_> = items.iter()
+            .filter(|(option, _)| !(app.is_read_only() && option.is_mutating()))
+            .collect();
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (_, text) in &items {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        println!();
+        match items[last_chosen].0 {
+            ApplyPending => run_confirmed(app, Direction::Up, plan_up(&migrations, &applied, None))?,
+            ApplyNext => run_confirmed(app, Direction::Up, plan_up(&migrations, &applied, Some(1)))?,
+            RevertLast => run_confirmed(app, Direction::Down, plan_down(&migrations, &applied, None)?)?,
+            DryRun => run_command(app, &Command { directory: directory.clone(), action: Action::Up(None), dry_run: true })?,
+            Back => return Ok(()),
+        }
+
+        println!();
+        wait_for_keypress();
+    }
+}
+
+// Every file is confirmed on its own, declining one stops before it
+fn run_confirmed(app: &App, direction: Direction, planned: Vec<&Migration>) -> Result<()> {
+    if planned.is_empty() {
+        println!("{}", direction.nothing_to_do());
+        return Ok(());
+    }
+
+    app.require_writable()?;
+    let connection = app.require_connection()?;
+
+    for migration in planned {
+        let sql = read_sql(migration, direction)?;
+
+        println!("-- {}", migration.file(direction)?.display());
+        if !ask_for_confirmation_before_query(app, &sql)? {
+            return Ok(());
+        }
+
+        apply(connection, migration, direction, &sql)?;
+        record_query(app, &sql);
+        println!("{} {}\n", direction.done(), migration.label());
+    }
+
+    Ok(())
+}
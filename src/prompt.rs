@@ -0,0 +1,472 @@
+// Prompts which can be left with ESC or Ctrl-C. They mirror the dialoguer builders used across the
+// crate but return crate::error::Result, with Error::Cancelled when the user backs out.
+//
+// dialoguer reads lines in cooked mode, where ESC is just another character and Ctrl-C kills the
+// process, so line input and confirmations read keys through crossterm in raw mode instead. When
+// stdin is not a terminal, e.g. with piped answers, whole lines are read without raw mode.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, IsTerminal};
+use std::str::FromStr;
+
+use console::Term;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use dialoguer::theme::Theme;
+
+use crate::error::{Error, Result};
+
+type Validate = dyn Fn(&str) -> Option<String>;
+
+// Words offered on Tab
+struct Completion {
+    words: Vec<String>,
+    // Names may contain spaces, they complete the whole input instead of the last word
+    whole_line: bool,
+}
+
+pub struct Input<'a, T> {
+    theme: &'a dyn Theme,
+    prompt: String,
+    default: Option<T>,
+    show_default: bool,
+    permit_empty: bool,
+    validator: Option<Box<Validate>>,
+    completion: Option<Completion>,
+}
+
+impl<'a, T> Input<'a, T>
+    where T: Clone + FromStr + Display, T::Err: Display
+{
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self {
+            theme,
+            prompt: String::new(),
+            default: None,
+            show_default: true,
+            permit_empty: false,
+            validator: None,
+            completion: None,
+        }
+    }
+
+    pub fn with_prompt(&mut self, prompt: &str) -> &mut Self {
+        self.prompt = prompt.to_string();
+        self
+    }
+
+    pub fn default(&mut self, value: T) -> &mut Self {
+        self.default = Some(value);
+        self
+    }
+
+    pub fn show_default(&mut self, val: bool) -> &mut Self {
+        self.show_default = val;
+        self
+    }
+
+    pub fn allow_empty(&mut self, val: bool) -> &mut Self {
+        self.permit_empty = val;
+        self
+    }
+
+    pub fn validate_with<V>(&mut self, validator: V) -> &mut Self
+        where V: dialoguer::Validator + 'static, V::Err: Display
+    {
+        self.validator = Some(Box::new(move |text| validator.validate(text).err().map(|err| err.to_string())));
+        self
+    }
+
+    /// Tab completes the whole input to one of the names.
+    pub fn completions(&mut self, names: Vec<String>) -> &mut Self {
+        self.completion = Some(Completion { words: names, whole_line: true });
+        self
+    }
+
+    /// Tab completes the word before the cursor, for SQL.
+    pub fn word_completions(&mut self, words: Vec<String>) -> &mut Self {
+        self.completion = Some(Completion { words, whole_line: false });
+        self
+    }
+
+    pub fn interact(&self) -> Result<T> {
+        let term = Term::stderr();
+        // lines written below the prompt start, cleared once the input is accepted
+        let mut height = 0;
+
+        loop {
+            let default = self.default.as_ref().filter(|_| self.show_default).map(|d| d.to_string());
+
+            let prompt = render(|f| self.theme.format_singleline_prompt(f, &self.prompt, default.as_deref()));
+            term.write_str(&prompt)?;
+
+            let (input, listed) = read_line(&term, true, self.completion.as_ref().map(|c| (c, prompt.as_str())))?;
+            term.write_line("")?;
+            height += 1 + listed;
+
+            if input.is_empty() {
+                if let Some(default) = &self.default {
+                    self.finish(&term, height, &default.to_string())?;
+                    return Ok(default.clone());
+                } else if !self.permit_empty {
+                    term.clear_last_lines(1)?;
+                    height -= 1;
+                    continue;
+                }
+            }
+
+            let error = match &self.validator {
+                Some(validator) => validator(&input),
+                None => None,
+            };
+
+            let error = match error {
+                Some(error) => error,
+                None => match input.parse::<T>() {
+                    Ok(value) => {
+                        self.finish(&term, height, &input)?;
+                        return Ok(value);
+                    },
+                    Err(err) => err.to_string(),
+                },
+            };
+
+            term.clear_last_lines(height)?;
+            let line = render(|f| self.theme.format_error(f, &error));
+            term.write_line(&line)?;
+            height = 1;
+        }
+    }
+
+    fn finish(&self, term: &Term, height: usize, selection: &str) -> Result<()> {
+        term.clear_last_lines(height)?;
+        let line = render(|f| self.theme.format_single_prompt_selection(f, &self.prompt, selection));
+        term.write_line(&line)?;
+        Ok(())
+    }
+}
+
+/// Line input which echoes nothing, for keys and passwords.
+pub struct Password<'a> {
+    theme: &'a dyn Theme,
+    prompt: String,
+    confirmation: Option<(String, String)>,
+}
+
+impl<'a> Password<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { theme, prompt: String::new(), confirmation: None }
+    }
+
+    pub fn with_prompt(&mut self, prompt: &str) -> &mut Self {
+        self.prompt = prompt.to_string();
+        self
+    }
+
+    /// Asks a second time with `prompt` and starts over with `mismatch_err` when the answers differ.
+    pub fn with_confirmation(&mut self, prompt: &str, mismatch_err: &str) -> &mut Self {
+        self.confirmation = Some((prompt.to_string(), mismatch_err.to_string()));
+        self
+    }
+
+    pub fn interact(&self) -> Result<String> {
+        let term = Term::stderr();
+
+        loop {
+            let password = self.ask(&term, &self.prompt)?;
+
+            if let Some((prompt, mismatch_err)) = &self.confirmation {
+                if self.ask(&term, prompt)? != password {
+                    let line = render(|f| self.theme.format_error(f, mismatch_err));
+                    term.write_line(&line)?;
+                    continue;
+                }
+            }
+
+            return Ok(password);
+        }
+    }
+
+    fn ask(&self, term: &Term, prompt: &str) -> Result<String> {
+        loop {
+            let line = render(|f| self.theme.format_singleline_prompt(f, prompt, None));
+            term.write_str(&line)?;
+
+            let (password, _) = read_line(term, false, None)?;
+            term.clear_line()?;
+
+            if !password.is_empty() {
+                let line = render(|f| self.theme.format_password_prompt_selection(f, prompt));
+                term.write_line(&line)?;
+                return Ok(password);
+            }
+        }
+    }
+}
+
+pub struct Select<'a> {
+    select: dialoguer::Select<'a>,
+}
+
+impl<'a> Select<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { select: dialoguer::Select::with_theme(theme) }
+    }
+
+    pub fn with_prompt(&mut self, prompt: &str) -> &mut Self {
+        self.select.with_prompt(prompt);
+        self
+    }
+
+    pub fn default(&mut self, val: usize) -> &mut Self {
+        self.select.default(val);
+        self
+    }
+
+    pub fn item(&mut self, item: &str) -> &mut Self {
+        self.select.item(item);
+        self
+    }
+
+    pub fn items<T: ToString>(&mut self, items: &[T]) -> &mut Self {
+        self.select.items(items);
+        self
+    }
+
+    // ESC and q are handled by dialoguer, Ctrl-C makes console raise SIGINT after the read fails.
+    pub fn interact(&self) -> Result<usize> {
+        let chosen = ignoring_interrupt(|| self.select.interact_opt())?;
+        chosen.ok_or(Error::Cancelled)
+    }
+}
+
+pub struct Confirmation<'a> {
+    theme: &'a dyn Theme,
+    text: String,
+    default: bool,
+}
+
+impl<'a> Confirmation<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { theme, text: String::new(), default: true }
+    }
+
+    pub fn with_text(&mut self, text: &str) -> &mut Self {
+        self.text = text.to_string();
+        self
+    }
+
+    pub fn default(&mut self, val: bool) -> &mut Self {
+        self.default = val;
+        self
+    }
+
+    pub fn interact(&self) -> Result<bool> {
+        let term = Term::stderr();
+
+        let prompt = render(|f| self.theme.format_confirmation_prompt(f, &self.text, Some(self.default)));
+        term.write_str(&prompt)?;
+
+        let answer = loop {
+            match read_key()? {
+                KeyCode::Char('y') | KeyCode::Char('Y') => break true,
+                KeyCode::Char('n') | KeyCode::Char('N') => break false,
+                KeyCode::Enter => break self.default,
+                _ => {},
+            }
+        };
+
+        term.clear_line()?;
+        let line = render(|f| self.theme.format_confirmation_prompt_selection(f, &self.text, answer));
+        term.write_line(&line)?;
+
+        Ok(answer)
+    }
+}
+
+/// Waits for a single key, ESC and Ctrl-C give Error::Cancelled.
+pub fn read_key() -> Result<KeyCode> {
+    // The first character of a piped line stands for the key, an empty line for Enter
+    if !io::stdin().is_terminal() {
+        return read_piped_line().map(|line| line.trim().chars().next().map_or(KeyCode::Enter, KeyCode::Char));
+    }
+
+    let _raw = RawMode::enable()?;
+    next_key()
+}
+
+fn next_key() -> Result<KeyCode> {
+    loop {
+        if let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? {
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+
+            return match code {
+                KeyCode::Esc => Err(Error::Cancelled),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => Err(Error::Cancelled),
+                code => Ok(code),
+            };
+        }
+    }
+}
+
+// Echoes typed characters, only appending and backspace are supported so wrapped lines stay correct.
+// With a completion Tab completes the input, a second Tab lists the candidates below and writes the
+// prompt again. Returns the line with count of lines written for the lists.
+fn read_line(term: &Term, echo: bool, completion: Option<(&Completion, &str)>) -> Result<(String, usize)> {
+    if !io::stdin().is_terminal() {
+        return read_piped_line().map(|line| (line, 0));
+    }
+
+    let _raw = RawMode::enable()?;
+    let mut line = String::new();
+    let mut listed = 0;
+    let mut last_was_tab = false;
+
+    loop {
+        let key = next_key()?;
+        let tab = key == KeyCode::Tab;
+
+        match key {
+            KeyCode::Enter => return Ok((line, listed)),
+            KeyCode::Backspace => {
+                let removed = line.pop();
+                if removed.is_some() && echo {
+                    term.clear_chars(1)?;
+                }
+            },
+            KeyCode::Char(c) => {
+                line.push(c);
+                if echo {
+                    term.write_str(c.encode_utf8(&mut [0; 4]))?;
+                }
+            },
+            KeyCode::Tab => if let Some((completion, prompt)) = completion {
+                let start = completion.word_start(&line);
+                let candidates = completion.candidates(&line[start..]);
+
+                match complete(&line[start..], &candidates) {
+                    Some(completed) => {
+                        term.clear_chars(line[start..].chars().count())?;
+                        line.truncate(start);
+                        line.push_str(&completed);
+                        term.write_str(&completed)?;
+                    },
+                    None if last_was_tab && candidates.len() > 1 => {
+                        let list = candidates.join("  ");
+                        let width = usize::from(term.size().1).max(1);
+                        term.write_str(&format!("\r\n{}\r\n{}{}", list, prompt, line))?;
+                        listed += 1 + console::measure_text_width(&list).div_ceil(width);
+                    },
+                    None => {},
+                }
+            },
+            _ => {},
+        }
+
+        last_was_tab = tab;
+    }
+}
+
+// Prompts would repeat forever at the end of input, so it cancels them like ESC
+fn read_piped_line() -> Result<String> {
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(Error::Cancelled);
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+impl Completion {
+    // Byte position where the completed part of the line starts
+    fn word_start(&self, line: &str) -> usize {
+        if self.whole_line {
+            return 0;
+        }
+
+        // Inside a quoted identifier the word starts at the quote
+        if line.matches('"').count() % 2 == 1 {
+            return line.rfind('"').unwrap_or(0);
+        }
+
+        line.char_indices().rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map_or(line.len(), |(i, _)| i)
+    }
+
+    // Identifiers are case insensitive, quoted words match without their quote too
+    fn candidates(&self, word: &str) -> Vec<&str> {
+        let word = word.to_lowercase();
+
+        self.words.iter()
+            .map(String::as_str)
+            .filter(|candidate| {
+                let candidate = candidate.to_lowercase();
+                candidate.starts_with(&word) || candidate.trim_start_matches('"').starts_with(&word)
+            })
+            .collect()
+    }
+}
+
+// A single candidate is taken as a whole, more only as far as they agree. None when nothing is added.
+fn complete(word: &str, candidates: &[&str]) -> Option<String> {
+    let first = candidates.first()?;
+    if candidates.len() == 1 {
+        return Some(first.to_string());
+    }
+
+    let common = candidates.iter().skip(1).fold(first.chars().count(), |common, candidate| {
+        first.chars().zip(candidate.chars())
+            .take(common)
+            .take_while(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+            .count()
+    });
+
+    if common > word.chars().count() {
+        Some(first.chars().take(common).collect())
+    } else {
+        None
+    }
+}
+
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Writing into a String cannot fail
+fn render<F>(f: F) -> String
+    where F: FnOnce(&mut dyn std::fmt::Write) -> std::fmt::Result
+{
+    let mut buffer = String::new();
+    let _ = f(&mut buffer);
+    buffer
+}
+
+#[cfg(unix)]
+fn ignoring_interrupt<T, F: FnOnce() -> T>(f: F) -> T {
+    unsafe {
+        let previous = libc::signal(libc::SIGINT, libc::SIG_IGN);
+        let result = f();
+        libc::signal(libc::SIGINT, previous);
+        result
+    }
+}
+
+#[cfg(not(unix))]
+fn ignoring_interrupt<T, F: FnOnce() -> T>(f: F) -> T {
+    f()
+}
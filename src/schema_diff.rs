@@ -0,0 +1,360 @@
+// Compares the schema of the open database with another file and writes a script which brings the
+// open database in line with the other one.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use console::style;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+
+use crate::app::App;
+use crate::db::{self, Column, quote_identifier};
+use crate::dump::read_shadow_tables;
+use crate::error::{Error, Result};
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, wait_for_keypress};
+
+#[derive(Clone)]
+pub struct SchemaObject {
+    pub kind: String,
+    pub name: String,
+    pub table: String,
+    pub sql: String,
+}
+
+pub enum Change {
+    // Only in the other database
+    Added,
+    // Only in the open database
+    Removed,
+    Changed(Vec<String>),
+}
+
+pub struct ObjectDiff {
+    pub kind: String,
+    pub name: String,
+    pub change: Change,
+    current: Option<SchemaObject>,
+    other: Option<SchemaObject>,
+    // Columns of the other table which exist in the current one, their data is kept on a rebuild
+    kept_columns: Vec<String>,
+}
+
+pub struct SchemaDiff {
+    pub objects: Vec<ObjectDiff>,
+    // Indexes and triggers of rebuilt tables are dropped with the old table and created again
+    rebuilt_dependents: Vec<SchemaObject>,
+}
+
+pub fn open_other(path: &str) -> Result<Connection> {
+    if !std::path::Path::new(path).exists() {
+        return Err(Error::validation(format!("Database file '{}' does not exist", path)));
+    }
+
+    Ok(Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?)
+}
+
+impl SchemaDiff {
+    pub fn compare(current: &Connection, other: &Connection) -> Result<Self> {
+        let current_objects = read_schema(current)?;
+        let other_objects = read_schema(other)?;
+
+        let find = |objects: &[SchemaObject], object: &SchemaObject| {
+            objects.iter().find(|o| o.kind == object.kind && o.name == object.name).cloned()
+        };
+
+        let mut objects = Vec::new();
+
+        for object in &current_objects {
+            match find(&other_objects, object) {
+                None => objects.push(ObjectDiff::new(object, Change::Removed, Some(object.clone()), None)),
+                Some(other_object) => {
+                    let details = if object.kind == "table" {
+                        compare_tables(current, other, object, &other_object)?
+                    } else if normalize(&object.sql) != normalize(&other_object.sql) {
+                        vec![String::from("definition changed")]
+                    } else {
+                        Vec::new()
+                    };
+
+                    if !details.is_empty() {
+                        let mut diff = ObjectDiff::new(object, Change::Changed(details), Some(object.clone()), Some(other_object.clone()));
+                        if object.kind == "table" {
+                            let current_columns = column_names(current, &object.name)?;
+                            diff.kept_columns = column_names(other, &object.name)?.into_iter()
+                                .filter(|column| current_columns.contains(column))
+                                .collect();
+                        }
+                        objects.push(diff);
+                    }
+                },
+            }
+        }
+
+        for object in &other_objects {
+            if find(&current_objects, object).is_none() {
+                objects.push(ObjectDiff::new(object, Change::Added, None, Some(object.clone())));
+            }
+        }
+
+        let rebuilt: HashSet<String> = objects.iter()
+            .filter(|diff| diff.kind == "table" && matches!(diff.change, Change::Changed(_)))
+            .map(|diff| diff.name.clone())
+            .collect();
+
+        let rebuilt_dependents: Vec<SchemaObject> = other_objects.iter()
+            .filter(|o| (o.kind == "index" || o.kind == "trigger") && rebuilt.contains(&o.table))
+            .cloned()
+            .collect();
+
+        Ok(SchemaDiff { objects, rebuilt_dependents })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn print_report(&self) {
+        if self.is_empty() {
+            println!("Schemas are the same");
+            return;
+        }
+
+        for diff in &self.objects {
+            let name = format!("{} {}", diff.kind, diff.name);
+            match &diff.change {
+                Change::Added => println!("{} {} (only in the other database)", style("+").green(), style(name).green()),
+                Change::Removed => println!("{} {} (only in the current database)", style("-").red(), style(name).red()),
+                Change::Changed(details) => {
+                    println!("{} {}", style("~").yellow(), style(name).yellow());
+                    for detail in details {
+                        println!("    {}", detail);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Statements which make the current schema the same as the other one. Tables which changed are
+    /// created again under the same name and rows are copied over for the columns both versions have.
+    pub fn write_migration<W: Write>(&self, out: &mut W) -> Result<()> {
+        let tables = |change: fn(&Change) -> bool| -> HashSet<&str> {
+            self.objects.iter()
+                .filter(|diff| diff.kind == "table" && change(&diff.change))
+                .map(|diff| diff.name.as_str())
+                .collect()
+        };
+        let dropped_tables = tables(|change| matches!(change, Change::Removed));
+        let rebuilt_tables = tables(|change| matches!(change, Change::Changed(_)));
+
+        // Changes of indexes and triggers on rebuilt tables are covered by creating them again after the rebuild
+        let rebuilt_tables = &rebuilt_tables;
+        let of_kind = |kind: &'static str| self.objects.iter().filter(move |diff| {
+            let table = diff.current.as_ref().or(diff.other.as_ref()).map_or("", |o| o.table.as_str());
+            diff.kind == kind && !((kind == "index" || kind == "trigger") && rebuilt_tables.contains(table))
+        });
+
+        writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+        // Renaming a table must not rewrite references to it in other tables, views and triggers
+        writeln!(out, "PRAGMA legacy_alter_table=ON;")?;
+        writeln!(out, "BEGIN TRANSACTION;")?;
+
+        // Views and triggers may use anything below, they are dropped first and created last
+        for kind in &["view", "trigger", "index"] {
+            for diff in of_kind(kind).filter(|diff| !matches!(diff.change, Change::Added)) {
+                let table = diff.current.as_ref().map(|o| o.table.as_str()).unwrap_or("");
+                if !dropped_tables.contains(table) || *kind == "view" {
+                    writeln!(out, "DROP {} IF EXISTS {};", kind.to_uppercase(), quote_identifier(&diff.name))?;
+                }
+            }
+        }
+
+        for diff in of_kind("table") {
+            match (&diff.change, &diff.current, &diff.other) {
+                (Change::Removed, _, _) => writeln!(out, "DROP TABLE {};", quote_identifier(&diff.name))?,
+                (Change::Added, _, Some(other)) => writeln!(out, "{};", other.sql)?,
+                (Change::Changed(_), Some(current), Some(other)) => write_rebuild(out, current, other, &diff.kept_columns)?,
+                _ => {},
+            }
+        }
+
+        for kind in &["index", "view", "trigger"] {
+            for diff in of_kind(kind).filter(|diff| !matches!(diff.change, Change::Removed)) {
+                if let Some(other) = &diff.other {
+                    writeln!(out, "{};", other.sql)?;
+                }
+            }
+
+            for object in self.rebuilt_dependents.iter().filter(|o| o.kind == *kind) {
+                writeln!(out, "{};", object.sql)?;
+            }
+        }
+
+        writeln!(out, "COMMIT;")?;
+        writeln!(out, "PRAGMA legacy_alter_table=OFF;")?;
+        writeln!(out, "PRAGMA foreign_key_check;")?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+impl ObjectDiff {
+    fn new(object: &SchemaObject, change: Change, current: Option<SchemaObject>, other: Option<SchemaObject>) -> Self {
+        Self { kind: object.kind.clone(), name: object.name.clone(), change, current, other, kept_columns: Vec::new() }
+    }
+}
+
+// Virtual tables cannot be renamed and copied into, they are created again empty
+fn write_rebuild<W: Write>(out: &mut W, current: &SchemaObject, other: &SchemaObject, kept_columns: &[String]) -> Result<()> {
+    let name = quote_identifier(&current.name);
+
+    if is_virtual(&current.sql) || is_virtual(&other.sql) {
+        writeln!(out, "DROP TABLE {};", name)?;
+        writeln!(out, "{};", other.sql)?;
+        return Ok(());
+    }
+
+    let old_name = quote_identifier(&format!("{}_old", current.name));
+    let columns: Vec<String> = kept_columns.iter().map(|column| quote_identifier(column)).collect();
+
+    writeln!(out, "ALTER TABLE {} RENAME TO {};", name, old_name)?;
+    writeln!(out, "{};", other.sql)?;
+    if !columns.is_empty() {
+        let columns = columns.join(", ");
+        writeln!(out, "INSERT INTO {} ({}) SELECT {} FROM {};", name, columns, columns, old_name)?;
+    }
+    writeln!(out, "DROP TABLE {};", old_name)?;
+
+    Ok(())
+}
+
+fn compare_tables(current: &Connection, other: &Connection, current_table: &SchemaObject, other_table: &SchemaObject) -> Result<Vec<String>> {
+    let current_columns = table_columns(current, &current_table.name)?;
+    let other_columns = table_columns(other, &other_table.name)?;
+    let mut details = Vec::new();
+
+    for column in &current_columns {
+        match other_columns.iter().find(|c| c.name == column.name) {
+            None => details.push(format!("column '{}' removed", column.name)),
+            Some(other_column) if describe(column) != describe(other_column) => {
+                details.push(format!("column '{}' changed: {} -> {}", column.name, describe(column), describe(other_column)));
+            },
+            Some(_) => {},
+        }
+    }
+
+    for column in other_columns.iter().filter(|c| !current_columns.iter().any(|current| current.name == c.name)) {
+        details.push(format!("column '{}' added: {}", column.name, describe(column)));
+    }
+
+    // Table constraints, column order or anything else the column list does not show
+    if details.is_empty() && normalize(&current_table.sql) != normalize(&other_table.sql) {
+        details.push(String::from("definition changed"));
+    }
+
+    Ok(details)
+}
+
+fn describe(column: &Column) -> String {
+    let mut description = if column.sqltype.is_empty() { String::from("no type") } else { column.sqltype.clone() };
+
+    if column.primary_key {
+        description.push_str(" PRIMARY KEY");
+    }
+    if column.not_null {
+        description.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        description.push_str(" DEFAULT ");
+        description.push_str(default);
+    }
+
+    description
+}
+
+fn table_columns(connection: &Connection, table: &str) -> Result<Vec<Column>> {
+    // Virtual tables without declared columns have no rows in table_info
+    match db::table_columns(connection, table) {
+        Err(Error::Validation(_)) => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+fn column_names(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    Ok(table_columns(connection, table)?.into_iter().map(|column| column.name).collect())
+}
+
+// Internal objects are left out, autoindexes belong to the table definition which is compared anyway
+fn read_schema(connection: &Connection) -> Result<Vec<SchemaObject>> {
+    let shadow_tables = read_shadow_tables(connection);
+
+    let mut statement = connection.prepare(
+        "SELECT type, name, tbl_name, sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' ORDER BY rowid"
+    )?;
+    let objects = statement.query_map(NO_PARAMS, |row| {
+        Ok(SchemaObject { kind: row.get(0)?, name: row.get(1)?, table: row.get(2)?, sql: row.get(3)? })
+    })?.collect::<rusqlite::Result<Vec<SchemaObject>>>()?;
+
+    Ok(objects.into_iter().filter(|o| !shadow_tables.contains(&o.name)).collect())
+}
+
+// Whitespace is kept by sqlite exactly as it was written
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn is_virtual(sql: &str) -> bool {
+    normalize(sql).to_uppercase().starts_with("CREATE VIRTUAL TABLE")
+}
+
+pub fn schema_diff(app: &App) -> Result<()> {
+    clear()?;
+    println!("Compare schema with another database\n");
+
+    let path: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Other database file")
+        .interact()?;
+
+    let other = open_other(&path)?;
+    let diff = SchemaDiff::compare(app.require_connection()?, &other)?;
+
+    println!();
+    diff.print_report();
+    println!();
+
+    if diff.is_empty() {
+        wait_for_keypress();
+        return Ok(());
+    }
+
+    let option = Select::with_theme(&app.view.dialog_theme)
+        .with_prompt("Migration script")
+        .default(0)
+        .item("Show")
+        .item("Save to a file")
+        .item("Back")
+        .interact()?;
+
+    match option {
+        0 => {
+            println!();
+            diff.write_migration(&mut std::io::stdout().lock())?;
+            println!();
+        },
+        1 => {
+            let script: String = Input::with_theme(&app.view.dialog_theme)
+                .with_prompt("Output file")
+                .default(String::from("migration.sql"))
+                .interact()?;
+
+            diff.write_migration(&mut BufWriter::new(File::create(&script)?))?;
+            println!("Migration script was written into '{}'\n", style(&script).green());
+        },
+        _ => return Ok(()),
+    }
+
+    wait_for_keypress();
+    Ok(())
+}
@@ -0,0 +1,362 @@
+// Search in the text of the active table. Without an index every text column is scanned for the
+// term ignoring case. A full-text index is an FTS5 table over chosen columns which reads the text
+// from the table itself (external content), triggers keep it up to date with every change.
+
+use console::style;
+use rusqlite::{Connection, OptionalExtension, params, types::Value};
+
+use crate::app::App;
+use crate::db::{self, ResultSet, quote_identifier, to_sqlite_literal};
+use crate::display::{draw_result_with, value_repr};
+use crate::error::{Error, Result};
+use crate::history::record_query;
+use crate::prompt::{Input, Select};
+use crate::utils::{clear, print_error, wait_for_keypress, ask_for_confirmation_before_query};
+use crate::value_parser::Affinity;
+
+// snippet() puts these around matches, they are replaced by highlighting before drawing
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+// Characters shown before a match which would be cut off in the grid
+const CONTEXT: usize = 5;
+
+/// FTS5 table made by create_index for a table.
+pub struct SearchIndex {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+fn index_name(table: &str) -> String {
+    format!("{}_fts", table)
+}
+
+pub fn find_index(connection: &Connection, table: &str) -> Result<Option<SearchIndex>> {
+    let name = index_name(table);
+    let sql: Option<String> = connection
+        .query_row("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?", params![name], |row| row.get(0))
+        .optional()?;
+
+    match sql {
+        Some(sql) if sql.to_lowercase().contains("using fts5") => {
+            let columns = db::table_columns(connection, &name)?.into_iter().map(|column| column.name).collect();
+            Ok(Some(SearchIndex { name, columns }))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Statements creating the index with its triggers and filling it from the table.
+pub fn create_index_script(table: &str, columns: &[String]) -> String {
+    let index = quote_identifier(&index_name(table));
+    let trigger = |event: &str| quote_identifier(&format!("{}_{}", index_name(table), event));
+    let names = columns.iter().map(|column| quote_identifier(column)).collect::<Vec<String>>().join(", ");
+    let values = |row: &str| columns.iter().map(|column| format!("{}.{}", row, quote_identifier(column))).collect::<Vec<String>>().join(", ");
+    let content = to_sqlite_literal(&Value::Text(table.to_string()));
+    let table = quote_identifier(table);
+
+    // External content tables are told about removed text with the special 'delete' command
+    let insert = format!("INSERT INTO {}(rowid, {}) VALUES (new.rowid, {});", index, names, values("new"));
+    let delete = format!("INSERT INTO {0}({0}, rowid, {1}) VALUES ('delete', old.rowid, {2});", index, names, values("old"));
+
+    [
+        format!("CREATE VIRTUAL TABLE {} USING fts5({}, content={});", index, names, content),
+        format!("CREATE TRIGGER {} AFTER INSERT ON {} BEGIN {} END;", trigger("insert"), table, insert),
+        format!("CREATE TRIGGER {} AFTER DELETE ON {} BEGIN {} END;", trigger("delete"), table, delete),
+        format!("CREATE TRIGGER {} AFTER UPDATE ON {} BEGIN {} {} END;", trigger("update"), table, delete, insert),
+        format!("INSERT INTO {0}({0}) VALUES ('rebuild');", index),
+    ].join("\n")
+}
+
+pub fn drop_index_script(table: &str) -> String {
+    let name = index_name(table);
+    let mut statements: Vec<String> = ["insert", "delete", "update"].iter()
+        .map(|event| format!("DROP TRIGGER IF EXISTS {};", quote_identifier(&format!("{}_{}", name, event))))
+        .collect();
+    statements.push(format!("DROP TABLE IF EXISTS {};", quote_identifier(&name)));
+    statements.join("\n")
+}
+
+// Savepoint works inside a transaction opened by the user as well as without one
+fn execute_script(connection: &Connection, script: &str) -> Result<()> {
+    connection.execute_batch("SAVEPOINT search_index")?;
+
+    if let Err(err) = connection.execute_batch(script) {
+        let _ = connection.execute_batch("ROLLBACK TO search_index; RELEASE search_index");
+        return Err(Error::in_statement(err, script));
+    }
+
+    connection.execute_batch("RELEASE search_index")?;
+    Ok(())
+}
+
+// Columns which may hold text, declared as text or without a type
+fn text_columns(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    Ok(db::table_columns(connection, table)?.into_iter()
+        .filter(|column| column.sqltype.is_empty() || Affinity::from_sql_type(&column.sqltype) == Affinity::Text)
+        .map(|column| column.name)
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SearchOption {
+    Search,
+    SearchWithSyntax,
+    CreateIndex,
+    RemoveIndex,
+    Back,
+}
+
+pub fn search_table(app: &App) -> Result<()> {
+    let table = app.require_active_table()?;
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+
+        let connection = app.require_connection()?;
+        let index = find_index(connection, table)?;
+        let columns = text_columns(connection, table)?;
+
+        println!("Search in '{}'", style(table).cyan());
+        match &index {
+            Some(index) => println!("Full-text index covers {}, terms are searched as a phrase\n", index.columns.join(", ")),
+            None => println!("Text is searched ignoring case in {}\n", if columns.is_empty() { String::from("no columns") } else { columns.join(", ") }),
+        }
+
+        use SearchOption::*;
+        let mut options = vec![(Search, "Search")];
+        if index.is_some() {
+            options.push((SearchWithSyntax, "Search with FTS5 syntax, e.g. word* OR \"some phrase\""));
+        }
+        if !app.is_read_only() {
+            match index {
+                Some(_) => options.push((RemoveIndex, "Remove full-text index")),
+                None => options.push((CreateIndex, "Create full-text index")),
+            }
+        }
+        options.push((Back, "Back"));
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen.min(options.len() - 1));
+        for (_, text) in &options {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        // Failures, e.g. a mistake in FTS5 syntax, are shown without leaving the search
+        let result = match options[last_chosen].0 {
+            Search => search(app, table, index.as_ref(), &columns, false),
+            SearchWithSyntax => search(app, table, index.as_ref(), &columns, true),
+            CreateIndex => create_index(app, table),
+            RemoveIndex => remove_index(app, table),
+            Back => { break; },
+        };
+
+        match result {
+            Err(err) if err.is_cancelled() => return Err(err),
+            Err(err) => print_error(&err),
+            Ok(()) => {},
+        }
+        wait_for_keypress();
+    }
+
+    Ok(())
+}
+
+// Without syntax the term is matched as one phrase, so e-mails or unbalanced quotes are not errors
+fn search(app: &App, table: &str, index: Option<&SearchIndex>, columns: &[String], syntax: bool) -> Result<()> {
+    let term: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("Search for")
+        .interact()?;
+    println!();
+
+    match index {
+        Some(index) if syntax => full_text_search(app, table, index, &term),
+        Some(index) => full_text_search(app, table, index, &phrase(&term)),
+        None => substring_search(app, table, columns, &term),
+    }
+}
+
+fn substring_search(app: &App, table: &str, columns: &[String], term: &str) -> Result<()> {
+    if columns.is_empty() {
+        return Err(Error::validation(format!("Table '{}' has no text columns", table)));
+    }
+
+    // lower() only folds ASCII letters, the highlighting below does the same
+    let condition = columns.iter()
+        .map(|column| format!("instr(lower({}), lower(?1)) > 0", quote_identifier(column)))
+        .collect::<Vec<String>>()
+        .join(" OR ");
+    let from = format!("FROM {} WHERE {}", quote_identifier(table), condition);
+
+    let result = fetch_matches(app, &format!("SELECT COUNT(*) {}", from), &format!("SELECT * {}", from), term)?;
+    draw_result_with(&app.config, &result, |i, value| match value {
+        Value::Text(text) if columns.contains(&result.columns[i].name) => highlight_substring(text, term, app.config.truncate_width),
+        _ => value_repr(&app.config, value),
+    })
+}
+
+fn full_text_search(app: &App, table: &str, index: &SearchIndex, term: &str) -> Result<()> {
+    let (table, name) = (quote_identifier(table), quote_identifier(&index.name));
+    let count = format!("SELECT COUNT(*) FROM {0} WHERE {0} MATCH ?1", name);
+    let rows = format!(
+        "SELECT snippet({0}, -1, char({2}), char({3}), '...', 12) AS snippet, {1}.* \
+         FROM {0} JOIN {1} ON {1}.rowid = {0}.rowid WHERE {0} MATCH ?1 ORDER BY rank",
+        name, table, MATCH_START as u32, MATCH_END as u32
+    );
+
+    let result = fetch_matches(app, &count, &rows, term)?;
+    draw_result_with(&app.config, &result, |i, value| match value {
+        Value::Text(text) if i == 0 => highlight_marked(text),
+        _ => value_repr(&app.config, value),
+    })
+}
+
+// FTS5 string, inside it quotes are doubled and everything else is taken literally
+fn phrase(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+// First page of rows matching the term, with their count printed above
+fn fetch_matches(app: &App, count: &str, rows: &str, term: &str) -> Result<ResultSet> {
+    let connection = app.require_connection()?;
+    let total: i64 = connection.query_row(count, params![term], |row| row.get(0)).map_err(|err| Error::in_statement(err, count))?;
+
+    let rows = format!("{} LIMIT {}", rows, app.config.page_size);
+    let result = db::fetch(connection, &rows, params![term])?;
+
+    if result.rows.len() < total as usize {
+        println!("{} rows match, first {} of them:", total, result.rows.len());
+    } else {
+        println!("{} rows match:", total);
+    }
+
+    Ok(result)
+}
+
+// Shows the match even when it is past the truncation width, with a few characters before it
+fn highlight_substring(text: &str, term: &str, width: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let term_length = term.chars().count();
+
+    let position = match text.to_ascii_lowercase().find(&term.to_ascii_lowercase()) {
+        Some(position) if term_length > 0 => text[..position].chars().count(),
+        _ => return chars.iter().take(width).collect(),
+    };
+
+    let start = if position + term_length > width { position.saturating_sub(CONTEXT) } else { 0 };
+    let end = chars.len().min(start + width);
+    let match_end = end.min(position + term_length);
+    let text = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+    format!(
+        "{}{}{}{}",
+        if start > 0 { "..." } else { "" },
+        text(start, position.min(end)),
+        style(text(position.min(end), match_end)).reverse(),
+        text(match_end, end),
+    )
+}
+
+fn highlight_marked(snippet: &str) -> String {
+    let mut out = String::with_capacity(snippet.len());
+
+    for (i, part) in snippet.split(MATCH_START).enumerate() {
+        match part.split_once(MATCH_END) {
+            Some((matched, rest)) if i > 0 => {
+                out.push_str(&style(matched).reverse().to_string());
+                out.push_str(rest);
+            },
+            _ => out.push_str(part),
+        }
+    }
+
+    out
+}
+
+fn create_index(app: &App, table: &str) -> Result<()> {
+    let connection = app.require_connection()?;
+    app.require_writable()?;
+
+    // Triggers and the external content both point at rows by rowid
+    if connection.prepare(&format!("SELECT rowid FROM {}", quote_identifier(table))).is_err() {
+        return Err(Error::validation(format!("Table '{}' has no rowid, a full-text index can't follow its rows", table)));
+    }
+
+    let columns = match choose_columns(app, &db::table_columns(connection, table)?.into_iter().map(|column| column.name).collect::<Vec<String>>(), &text_columns(connection, table)?)? {
+        Some(columns) => columns,
+        None => return Ok(()),
+    };
+
+    let script = create_index_script(table, &columns);
+    if !ask_for_confirmation_before_query(app, &script)? {
+        return Ok(());
+    }
+
+    execute_script(connection, &script)?;
+    record_query(app, &script);
+    println!("Full-text index {} was created\n", index_name(table));
+    Ok(())
+}
+
+fn remove_index(app: &App, table: &str) -> Result<()> {
+    app.require_writable()?;
+
+    let script = drop_index_script(table);
+    if !ask_for_confirmation_before_query(app, &script)? {
+        return Ok(());
+    }
+
+    execute_script(app.require_connection()?, &script)?;
+    record_query(app, &script);
+    println!("Full-text index {} was removed\n", index_name(table));
+    Ok(())
+}
+
+// Toggles columns on and off, text columns are chosen at first. None when cancelled.
+fn choose_columns(app: &App, columns: &[String], text_columns: &[String]) -> Result<Option<Vec<String>>> {
+    let mut chosen: Vec<bool> = columns.iter().map(|column| text_columns.contains(column)).collect();
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+        println!("Choose columns of the full-text index\n");
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (column, on) in columns.iter().zip(&chosen) {
+            select.item(format!("[{}] {}", if *on { "x" } else { " " }, column).as_str());
+        }
+        select.item("Create index");
+        select.item("Cancel");
+        last_chosen = select.interact()?;
+
+        match last_chosen {
+            i if i < columns.len() => chosen[i] = !chosen[i],
+            i if i == columns.len() => {
+                let picked: Vec<String> = columns.iter().zip(&chosen).filter(|(_, on)| **on).map(|(column, _)| column.clone()).collect();
+                if !picked.is_empty() {
+                    return Ok(Some(picked));
+                }
+            },
+            _ => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phrases_match_terms_with_syntax_characters() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch("CREATE VIRTUAL TABLE t USING fts5(a); INSERT INTO t VALUES('mail user@example.com about foo-bar \"x');").unwrap();
+
+        for term in ["user@example.com", "foo-bar", "\"x", "MAIL"] {
+            let count: i64 = connection.query_row("SELECT COUNT(*) FROM t WHERE t MATCH ?1", params![phrase(term)], |row| row.get(0)).unwrap();
+            assert_eq!(count, 1, "{}", term);
+        }
+    }
+}
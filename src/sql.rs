@@ -0,0 +1,482 @@
+// Lightweight SQL tokenizer for showing statements before they run. It only has to be good enough
+// to split clauses and pick colors, statements are still parsed by sqlite itself.
+
+use console::Style;
+
+use crate::config::ThemeConfig;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    // "name", [name] or `name`
+    QuotedIdentifier,
+    // Strings, numbers and blobs
+    Literal,
+    // ?, ?1, :name, @name or $name
+    Parameter,
+    Comment,
+    Whitespace,
+    Punctuation,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+const KEYWORDS: &[&str] = &[
+    "ABORT", "ACTION", "ADD", "AFTER", "ALL", "ALTER", "ALWAYS", "ANALYZE", "AND", "AS", "ASC",
+    "ATTACH", "AUTOINCREMENT", "BEFORE", "BEGIN", "BETWEEN", "BY", "CASCADE", "CASE", "CAST",
+    "CHECK", "COLLATE", "COLUMN", "COMMIT", "CONFLICT", "CONSTRAINT", "CREATE", "CROSS", "CURRENT",
+    "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "DATABASE", "DEFAULT", "DEFERRABLE",
+    "DEFERRED", "DELETE", "DESC", "DETACH", "DISTINCT", "DO", "DROP", "EACH", "ELSE", "END",
+    "ESCAPE", "EXCEPT", "EXCLUSIVE", "EXISTS", "EXPLAIN", "FAIL", "FALSE", "FILTER", "FOLLOWING",
+    "FOR", "FOREIGN", "FROM", "FULL", "GENERATED", "GLOB", "GROUP", "HAVING", "IF", "IGNORE",
+    "IMMEDIATE", "IN", "INDEX", "INITIALLY", "INNER", "INSERT", "INSTEAD", "INTERSECT", "INTO",
+    "IS", "ISNULL", "JOIN", "KEY", "LEFT", "LIKE", "LIMIT", "MATCH", "NATURAL", "NO", "NOT",
+    "NOTHING", "NOTNULL", "NULL", "OF", "OFFSET", "ON", "OR", "ORDER", "OVER", "PARTITION",
+    "PRAGMA", "PRIMARY", "RECURSIVE", "REFERENCES", "REINDEX", "RELEASE", "RENAME", "REPLACE",
+    "RESTRICT", "RETURNING", "RIGHT", "ROLLBACK", "ROWID", "ROWS", "SAVEPOINT", "SELECT", "SET",
+    "TABLE", "TEMP", "TEMPORARY", "THEN", "TO", "TRANSACTION", "TRIGGER", "TRUE", "UNION", "UNIQUE",
+    "UPDATE", "USING", "VACUUM", "VALUES", "VIEW", "VIRTUAL", "WHEN", "WHERE", "WINDOW", "WITH",
+    "WITHOUT",
+];
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.binary_search(&word.to_uppercase().as_str()).is_ok()
+}
+
+pub fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let chars: Vec<(usize, char)> = sql.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(sql.len(), |(byte, _)| *byte);
+    let char_at = |i: usize| chars.get(i).map(|(_, c)| *c);
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while let Some(c) = char_at(i) {
+        let start = i;
+        let next = char_at(i + 1);
+
+        let kind = match c {
+            c if c.is_whitespace() => {
+                while char_at(i).is_some_and(char::is_whitespace) { i += 1; }
+                TokenKind::Whitespace
+            },
+            '-' if next == Some('-') => {
+                while char_at(i).is_some_and(|c| c != '\n') { i += 1; }
+                TokenKind::Comment
+            },
+            '/' if next == Some('*') => {
+                i += 2;
+                while char_at(i).is_some() && !(char_at(i) == Some('*') && char_at(i + 1) == Some('/')) { i += 1; }
+                i = (i + 2).min(chars.len());
+                TokenKind::Comment
+            },
+            '\'' => {
+                i = skip_quoted(&char_at, i, '\'');
+                TokenKind::Literal
+            },
+            'x' | 'X' if next == Some('\'') => {
+                i = skip_quoted(&char_at, i + 1, '\'');
+                TokenKind::Literal
+            },
+            '"' | '`' => {
+                i = skip_quoted(&char_at, i, c);
+                TokenKind::QuotedIdentifier
+            },
+            '[' => {
+                while char_at(i).is_some_and(|c| c != ']') { i += 1; }
+                i = (i + 1).min(chars.len());
+                TokenKind::QuotedIdentifier
+            },
+            c if c.is_ascii_digit() || (c == '.' && next.is_some_and(|c| c.is_ascii_digit())) => {
+                while let Some(c) = char_at(i) {
+                    let exponent_sign = (c == '+' || c == '-') && matches!(char_at(i - 1), Some('e') | Some('E'))
+                        && !sql[byte_at(start)..byte_at(i)].starts_with("0x");
+                    if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) { break; }
+                    i += 1;
+                }
+                TokenKind::Literal
+            },
+            '?' => {
+                i += 1;
+                while char_at(i).is_some_and(|c| c.is_ascii_digit()) { i += 1; }
+                TokenKind::Parameter
+            },
+            ':' | '@' | '$' if next.is_some_and(is_word) => {
+                i += 1;
+                while char_at(i).is_some_and(is_word) { i += 1; }
+                TokenKind::Parameter
+            },
+            c if is_word(c) => {
+                while char_at(i).is_some_and(is_word) { i += 1; }
+                if is_keyword(&sql[byte_at(start)..byte_at(i)]) { TokenKind::Keyword } else { TokenKind::Identifier }
+            },
+            _ => {
+                i += 1;
+                TokenKind::Punctuation
+            },
+        };
+
+        tokens.push(Token { kind, text: &sql[byte_at(start)..byte_at(i)] });
+    }
+
+    tokens
+}
+
+// Index after the closing quote, a doubled quote stands for the quote itself
+fn skip_quoted<F: Fn(usize) -> Option<char>>(char_at: &F, open: usize, quote: char) -> usize {
+    let mut i = open + 1;
+
+    while let Some(c) = char_at(i) {
+        i += 1;
+        if c == quote {
+            if char_at(i) == Some(quote) {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    i
+}
+
+// Clauses which start a new line when they are not inside parentheses
+const CLAUSES: [&str; 14] = [
+    "SELECT", "FROM", "WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "VALUES", "SET", "UNION",
+    "EXCEPT", "INTERSECT", "RETURNING", "WINDOW",
+];
+
+// Joins start a new line at their first word
+const JOIN_PREFIXES: [&str; 6] = ["LEFT", "RIGHT", "FULL", "INNER", "CROSS", "NATURAL"];
+
+/// Puts clauses on their own lines and every column of CREATE TABLE on its own indented line.
+/// Any whitespace between tokens, line breaks included, becomes a single space and no space is added
+/// where there was none. Literals and comments are kept as written.
+pub fn format(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len() + 32);
+    let mut depth = 0usize;
+    let mut pending_space = false;
+    // Keywords from the start of the current statement, to recognize CREATE TABLE
+    let mut statement: Vec<String> = Vec::new();
+    let mut previous_keyword = String::new();
+    let mut in_columns = false;
+
+    let newline = |out: &mut String, indent: usize| {
+        while out.ends_with(' ') { out.pop(); }
+        out.push('\n');
+        out.push_str(&"    ".repeat(indent));
+    };
+    let at_line_start = |out: &String| out.is_empty() || out.ends_with('\n') || out.ends_with("    ");
+
+    for token in tokenize(sql) {
+        if token.kind == TokenKind::Whitespace {
+            pending_space = true;
+            continue;
+        }
+
+        let upper = token.text.to_uppercase();
+        let is_keyword = token.kind == TokenKind::Keyword;
+
+        let starts_clause = is_keyword && depth == 0 && !statement.is_empty() && match upper.as_str() {
+            "FROM" => previous_keyword != "DELETE",
+            "JOIN" => !JOIN_PREFIXES.contains(&previous_keyword.as_str()),
+            word => CLAUSES.contains(&word) || JOIN_PREFIXES.contains(&word),
+        };
+
+        let closes_columns = in_columns && depth == 1 && token.text == ")";
+
+        if starts_clause || closes_columns {
+            newline(&mut out, 0);
+        } else if pending_space && !at_line_start(&out) {
+            out.push(' ');
+        }
+        pending_space = false;
+
+        out.push_str(token.text);
+
+        if is_keyword {
+            previous_keyword = upper.clone();
+        }
+        if statement.len() < 4 && token.kind != TokenKind::Comment {
+            statement.push(upper);
+        }
+
+        match token.text {
+            "(" => {
+                depth += 1;
+                if depth == 1 && !in_columns && is_create_table(&statement) {
+                    in_columns = true;
+                    newline(&mut out, 1);
+                }
+            },
+            ")" => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    in_columns = false;
+                }
+            },
+            "," if in_columns && depth == 1 => newline(&mut out, 1),
+            ";" if depth == 0 => {
+                out.push('\n');
+                statement.clear();
+                previous_keyword.clear();
+            },
+            _ if token.kind == TokenKind::Comment && token.text.starts_with("--") => out.push('\n'),
+            _ => {},
+        }
+    }
+
+    out.trim_end().to_string()
+}
+
+fn is_create_table(statement: &[String]) -> bool {
+    match statement {
+        [create, table, ..] if create == "CREATE" && table == "TABLE" => true,
+        [create, temp, table, ..] if create == "CREATE" && (temp == "TEMP" || temp == "TEMPORARY") && table == "TABLE" => true,
+        _ => false,
+    }
+}
+
+/// Rows changed by a single DELETE or UPDATE, both parts are SQL text as written in the statement.
+pub struct Target {
+    // Table with its schema, alias or INDEXED BY, usable after FROM of a SELECT
+    pub table: String,
+    pub condition: Option<String>,
+    // ORDER BY and LIMIT, with them only some of the matching rows change
+    pub limit: Option<String>,
+}
+
+/// Finds what a DELETE or UPDATE changes. None for other statements and for ones which can't be
+/// previewed by a plain SELECT: several statements, parameters, WITH or UPDATE ... FROM.
+pub fn target(sql: &str) -> Option<Target> {
+    let tokens: Vec<Token> = tokenize(sql).into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect();
+    if tokens.iter().any(|token| token.kind == TokenKind::Parameter) {
+        return None;
+    }
+
+    let significant: Vec<(usize, String)> = tokens.iter().enumerate()
+        .filter(|(_, token)| token.kind != TokenKind::Whitespace)
+        .map(|(i, token)| (i, token.text.to_uppercase()))
+        .collect();
+    let words: Vec<&str> = significant.iter().map(|(_, word)| word.as_str()).collect();
+    let text = |from: usize, to: usize| tokens[from..to].iter().map(|token| token.text).collect::<String>().trim().to_string();
+
+    // Position of the first table token, the table of a DELETE ends at WHERE, of an UPDATE at SET
+    let (table_start, is_update) = match words.as_slice() {
+        ["DELETE", "FROM", ..] => (2, false),
+        ["UPDATE", "OR", _, ..] => (3, true),
+        ["UPDATE", ..] => (1, true),
+        _ => return None,
+    };
+
+    let mut depth = 0usize;
+    let mut table_end = None;
+    let mut condition_start = None;
+    let mut condition_end = tokens.len();
+    let mut limit_start = None;
+    let mut limit_end = tokens.len();
+
+    for (position, (index, word)) in significant.iter().enumerate().skip(table_start) {
+        match word.as_str() {
+            "(" => depth += 1,
+            ")" => depth = depth.saturating_sub(1),
+            ";" if depth == 0 => {
+                // Anything but trailing semicolons is another statement
+                if words[position..].iter().any(|word| *word != ";") {
+                    return None;
+                }
+                condition_end = condition_end.min(*index);
+                limit_end = limit_end.min(*index);
+                break;
+            },
+            "SET" if depth == 0 && is_update && table_end.is_none() => table_end = Some(*index),
+            "FROM" if depth == 0 && is_update && condition_start.is_none() => return None,
+            "WHERE" if depth == 0 && condition_start.is_none() => {
+                table_end.get_or_insert(*index);
+                condition_start = Some(*index + 1);
+            },
+            "ORDER" | "LIMIT" if depth == 0 => {
+                condition_end = condition_end.min(*index);
+                limit_start.get_or_insert(*index);
+            },
+            "RETURNING" if depth == 0 => {
+                condition_end = condition_end.min(*index);
+                limit_end = limit_end.min(*index);
+            },
+            _ => {},
+        }
+    }
+
+    let first_table_token = significant[table_start..].first()?.0;
+    let table = text(first_table_token, table_end.unwrap_or(condition_end));
+    if table.is_empty() {
+        return None;
+    }
+
+    Some(Target {
+        table,
+        condition: condition_start.map(|start| text(start, condition_end)).filter(|condition| !condition.is_empty()),
+        limit: limit_start.map(|start| text(start, limit_end)).filter(|limit| !limit.is_empty()),
+    })
+}
+
+/// Colors of highlighted SQL, taken from the theme in the settings.
+pub struct SqlStyle {
+    pub keyword: Style,
+    pub identifier: Style,
+    pub literal: Style,
+    pub comment: Style,
+}
+
+impl SqlStyle {
+    pub fn new(theme: &ThemeConfig) -> Self {
+        Self {
+            keyword: Style::from_dotted_str(&theme.keyword),
+            identifier: Style::from_dotted_str(&theme.identifier),
+            literal: Style::from_dotted_str(&theme.literal),
+            comment: Style::from_dotted_str(&theme.comment),
+        }
+    }
+}
+
+/// Colors every token, the text is unchanged when colors are off, e.g. stdout is not a terminal.
+pub fn highlight(style: &SqlStyle, sql: &str) -> String {
+    if !console::colors_enabled() {
+        return sql.to_string();
+    }
+
+    tokenize(sql).iter()
+        .map(|token| match token.kind {
+            TokenKind::Keyword => style.keyword.apply_to(token.text).to_string(),
+            TokenKind::Identifier | TokenKind::QuotedIdentifier => style.identifier.apply_to(token.text).to_string(),
+            TokenKind::Literal | TokenKind::Parameter => style.literal.apply_to(token.text).to_string(),
+            TokenKind::Comment => style.comment.apply_to(token.text).to_string(),
+            TokenKind::Whitespace | TokenKind::Punctuation => token.text.to_string(),
+        })
+        .collect()
+}
+
+/// Formatted and highlighted, for previews of statements.
+pub fn pretty(style: &SqlStyle, sql: &str) -> String {
+    highlight(style, &format(sql))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(sql).into_iter()
+            .filter(|token| token.kind != TokenKind::Whitespace)
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_keep_the_text() {
+        let sql = "SELECT \"a b\", [c], 'it''s', x'00', 1.5e3 -- note\nFROM t WHERE a = :name /* c */;";
+        let joined: String = tokenize(sql).iter().map(|token| token.text).collect();
+        assert_eq!(joined, sql);
+    }
+
+    #[test]
+    fn token_kinds() {
+        use TokenKind::*;
+
+        assert_eq!(kinds("SELECT \"a b\", [c], `d` FROM t"), vec![
+            (Keyword, "SELECT"), (QuotedIdentifier, "\"a b\""), (Punctuation, ","), (QuotedIdentifier, "[c]"),
+            (Punctuation, ","), (QuotedIdentifier, "`d`"), (Keyword, "FROM"), (Identifier, "t"),
+        ]);
+        assert_eq!(kinds("'it''s' x'0a' 12 -3.5e2"), vec![
+            (Literal, "'it''s'"), (Literal, "x'0a'"), (Literal, "12"), (Punctuation, "-"), (Literal, "3.5e2"),
+        ]);
+        assert_eq!(kinds("? ?2 :a @b $c"), vec![
+            (Parameter, "?"), (Parameter, "?2"), (Parameter, ":a"), (Parameter, "@b"), (Parameter, "$c"),
+        ]);
+        assert_eq!(kinds("a -- x\n/* y */"), vec![(Identifier, "a"), (Comment, "-- x"), (Comment, "/* y */")]);
+    }
+
+    #[test]
+    fn unterminated_tokens_run_to_the_end() {
+        assert_eq!(kinds("SELECT 'abc"), vec![(TokenKind::Keyword, "SELECT"), (TokenKind::Literal, "'abc")]);
+        assert_eq!(kinds("/* abc"), vec![(TokenKind::Comment, "/* abc")]);
+    }
+
+    #[test]
+    fn clauses_start_lines() {
+        assert_eq!(
+            format("select a, b from t left join u on t.id = u.id where a > 1 order by b limit 5"),
+            "select a, b\nfrom t\nleft join u on t.id = u.id\nwhere a > 1\norder by b\nlimit 5"
+        );
+        assert_eq!(format("DELETE FROM t WHERE a = 1"), "DELETE FROM t\nWHERE a = 1");
+        assert_eq!(format("UPDATE t SET a = 1 WHERE b = 2"), "UPDATE t\nSET a = 1\nWHERE b = 2");
+    }
+
+    #[test]
+    fn subqueries_stay_on_their_line() {
+        assert_eq!(
+            format("SELECT * FROM t WHERE a IN (SELECT a FROM u WHERE b)"),
+            "SELECT *\nFROM t\nWHERE a IN (SELECT a FROM u WHERE b)"
+        );
+    }
+
+    #[test]
+    fn create_table_puts_columns_on_lines() {
+        assert_eq!(
+            format("CREATE TABLE t(a INTEGER, b TEXT DEFAULT (1), c)"),
+            "CREATE TABLE t(\n    a INTEGER,\n    b TEXT DEFAULT (1),\n    c\n)"
+        );
+    }
+
+    #[test]
+    fn statements_are_separated() {
+        assert_eq!(format("DELETE FROM a; DELETE FROM b;"), "DELETE FROM a;\nDELETE FROM b;");
+    }
+
+    fn target_parts(sql: &str) -> Option<(String, Option<String>, Option<String>)> {
+        target(sql).map(|target| (target.table, target.condition, target.limit))
+    }
+
+    fn parts(table: &str, condition: Option<&str>, limit: Option<&str>) -> Option<(String, Option<String>, Option<String>)> {
+        Some((table.to_string(), condition.map(String::from), limit.map(String::from)))
+    }
+
+    #[test]
+    fn delete_target() {
+        assert_eq!(target_parts("DELETE FROM t"), parts("t", None, None));
+        assert_eq!(target_parts("delete from \"my t\" where a = 1;"), parts("\"my t\"", Some("a = 1"), None));
+        assert_eq!(
+            target_parts("DELETE FROM main.t WHERE a IN (SELECT a FROM u ORDER BY a LIMIT 1) ORDER BY a LIMIT 5 RETURNING *"),
+            parts("main.t", Some("a IN (SELECT a FROM u ORDER BY a LIMIT 1)"), Some("ORDER BY a LIMIT 5"))
+        );
+        assert_eq!(target_parts("DELETE FROM t LIMIT 2"), parts("t", None, Some("LIMIT 2")));
+    }
+
+    #[test]
+    fn update_target() {
+        assert_eq!(target_parts("UPDATE t SET a = 1"), parts("t", None, None));
+        assert_eq!(target_parts("UPDATE OR IGNORE t AS x SET a = 1 WHERE x.b = 'WHERE'"), parts("t AS x", Some("x.b = 'WHERE'"), None));
+        assert_eq!(
+            target_parts("UPDATE t SET a = (SELECT max(a) FROM u) WHERE b > 2 RETURNING a"),
+            parts("t", Some("b > 2"), None)
+        );
+    }
+
+    #[test]
+    fn statements_without_target() {
+        assert!(target("SELECT * FROM t").is_none());
+        assert!(target("DELETE FROM t WHERE a = ?").is_none());
+        assert!(target("DELETE FROM t WHERE a = :a").is_none());
+        assert!(target("DELETE FROM t; DELETE FROM u").is_none());
+        assert!(target("WITH x AS (SELECT 1) DELETE FROM t").is_none());
+        assert!(target("UPDATE t SET a = u.a FROM u WHERE t.id = u.id").is_none());
+    }
+}
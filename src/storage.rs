@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use console::style;
+
+use crate::app::App;
+use crate::db::{self, STORAGE_REPORT_COLUMNS};
+use crate::display::{draw_result, set_sorting_options};
+use crate::error::Result;
+use crate::prompt::Select;
+use crate::utils::{clear, format_size};
+
+pub fn storage_report(app: &App) -> Result<()> {
+    let columns: Vec<String> = STORAGE_REPORT_COLUMNS.iter().map(|column| column.to_string()).collect();
+
+    // Biggest objects first, they are what the report is usually opened for
+    let mut sorting_options: HashMap<String, bool> = HashMap::new();
+    sorting_options.insert(String::from("bytes"), false);
+
+    loop {
+        clear()?;
+
+        println!("Storage used by tables and indexes, database file takes {}\n", style(format_size(app.database_size()?)).cyan());
+
+        let sorting: Vec<(&str, bool)> = sorting_options.iter().map(|(column, ascending)| (column.as_str(), *ascending)).collect();
+        if !sorting.is_empty() {
+            println!("{}", db::order_by(&sorting).trim_start());
+        }
+
+        let report = db::storage_report(app.require_connection()?, &sorting)?;
+        draw_result(&app.config, &report)?;
+        println!();
+
+        let option = Select::with_theme(&app.view.dialog_theme)
+            .default(0)
+            .item("Define sorting criteria")
+            .item("Back")
+            .interact()?;
+
+        match option {
+            0 => set_sorting_options(app, &columns, &mut sorting_options)?,
+            _ => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+// Report grouping rows of the active table by chosen columns with aggregates over the others.
+// It pages and sorts like the table view, sorting and HAVING can refer to aggregates by label.
+
+use std::collections::HashMap;
+
+use rusqlite::params;
+
+use crate::app::App;
+use crate::db::{self, quote_identifier};
+use crate::display::{draw_query, set_sorting_options};
+use crate::error::Result;
+use crate::insert_row::get_table;
+use crate::prompt::{Input, Select};
+use crate::sql::pretty;
+use crate::utils::{clear, print_error, wait_for_keypress, CONDITION_KEYWORDS};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Aggregate {
+    Count,
+    CountDistinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    GroupConcat,
+}
+
+impl Aggregate {
+    const ALL: [Aggregate; 7] = [
+        Aggregate::Count, Aggregate::CountDistinct, Aggregate::Sum, Aggregate::Avg,
+        Aggregate::Min, Aggregate::Max, Aggregate::GroupConcat,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Count => "COUNT",
+            Aggregate::CountDistinct => "COUNT DISTINCT",
+            Aggregate::Sum => "SUM",
+            Aggregate::Avg => "AVG",
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+            Aggregate::GroupConcat => "GROUP_CONCAT",
+        }
+    }
+
+    // None stands for every row, only COUNT takes it
+    fn expression(self, column: Option<&str>) -> String {
+        let column = column.map_or_else(|| String::from("*"), quote_identifier);
+
+        match self {
+            Aggregate::CountDistinct => format!("COUNT(DISTINCT {})", column),
+            Aggregate::GroupConcat => format!("GROUP_CONCAT({}, ', ')", column),
+            _ => format!("{}({})", self.name(), column),
+        }
+    }
+
+    fn label(self, column: Option<&str>) -> String {
+        format!("{}({})", self.name().to_lowercase().replace(' ', "_"), column.unwrap_or("*"))
+    }
+}
+
+/// Grouping and aggregates chosen by the user. Without aggregates groups are counted.
+#[derive(Default)]
+struct Report {
+    group_by: Vec<String>,
+    aggregates: Vec<(Aggregate, Option<String>)>,
+    having: Option<String>,
+    sorting: HashMap<String, bool>,
+}
+
+impl Report {
+    fn aggregates(&self) -> Vec<(Aggregate, Option<&str>)> {
+        match self.aggregates.is_empty() {
+            true => vec![(Aggregate::Count, None)],
+            false => self.aggregates.iter().map(|(aggregate, column)| (*aggregate, column.as_deref())).collect(),
+        }
+    }
+
+    // Names of the report columns, usable in sorting and HAVING
+    fn labels(&self) -> Vec<String> {
+        self.group_by.iter().cloned()
+            .chain(self.aggregates().into_iter().map(|(aggregate, column)| aggregate.label(column)))
+            .collect()
+    }
+
+    /// Statement with LIMIT and OFFSET left as parameters.
+    fn sql(&self, table: &str) -> String {
+        let group_by: Vec<String> = self.group_by.iter().map(|column| quote_identifier(column)).collect();
+        let mut selected = group_by.clone();
+        selected.extend(self.aggregates().into_iter()
+            .map(|(aggregate, column)| format!("{} AS {}", aggregate.expression(column), quote_identifier(&aggregate.label(column)))));
+
+        let mut sql = format!("SELECT {} FROM {}", selected.join(", "), quote_identifier(table));
+        if !group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+        }
+        if let Some(having) = &self.having {
+            sql.push_str(&format!(" HAVING {}", having));
+        }
+
+        let sorting: Vec<(&str, bool)> = self.sorting.iter().map(|(column, ascending)| (column.as_str(), *ascending)).collect();
+        sql.push_str(&db::order_by(&sorting));
+        sql.push_str(" LIMIT ? OFFSET ?");
+        sql
+    }
+
+    // Sorting by a column which is gone from the report would fail
+    fn forget_missing_labels(&mut self) {
+        let labels = self.labels();
+        self.sorting.retain(|column, _| labels.contains(column));
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SummaryOption {
+    NextPage,
+    PreviousPage,
+    GroupBy,
+    AddAggregate,
+    RemoveAggregates,
+    Having,
+    DefineSorting,
+    Back,
+}
+
+pub fn summarize(app: &App) -> Result<()> {
+    let table = app.require_active_table()?;
+    let columns: Vec<String> = get_table(app)?.into_iter().map(|column| column.name).collect();
+    let rows_per_page = app.config.page_size;
+
+    let mut report = Report::default();
+    let mut page: usize = 0;
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+
+        println!("Summary of '{}', groups from {} to {}", table, page * rows_per_page, (page + 1) * rows_per_page);
+        let sql = report.sql(table);
+        println!("{}\n", pretty(&app.view.sql_style, &sql));
+        draw_query(app, &sql, params![rows_per_page as i64, (page * rows_per_page) as i64])?;
+        println!();
+
+        use SummaryOption::*;
+        let mut options = vec![
+            (NextPage, format!("Next {} groups", rows_per_page)),
+            (PreviousPage, format!("Previous {} groups", rows_per_page)),
+            (GroupBy, String::from("Choose columns to group by")),
+            (AddAggregate, String::from("Add aggregate")),
+            (RemoveAggregates, String::from("Remove aggregates")),
+            (Having, String::from("Filter groups with HAVING")),
+            (DefineSorting, String::from("Define sorting criteria")),
+            (Back, String::from("Back")),
+        ];
+        if report.aggregates.is_empty() {
+            options.retain(|(option, _)| *option != RemoveAggregates);
+        }
+        last_chosen = last_chosen.min(options.len() - 1);
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for (_, text) in &options {
+            select.item(text);
+        }
+        last_chosen = select.interact()?;
+
+        match options[last_chosen].0 {
+            NextPage => { page += 1; },
+            PreviousPage => { page = page.saturating_sub(1); },
+            GroupBy => {
+                choose_group_by(app, &columns, &mut report)?;
+                page = 0;
+            },
+            AddAggregate => { add_aggregate(app, &columns, &mut report)?; },
+            RemoveAggregates => {
+                report.aggregates.clear();
+                report.having = None;
+                report.forget_missing_labels();
+            },
+            Having => {
+                set_having(app, table, &mut report)?;
+                page = 0;
+            },
+            DefineSorting => { set_sorting_options(app, &report.labels(), &mut report.sorting)?; },
+            Back => { break; },
+        }
+    }
+
+    Ok(())
+}
+
+fn choose_group_by(app: &App, columns: &[String], report: &mut Report) -> Result<()> {
+    let mut last_chosen = 0;
+
+    loop {
+        clear()?;
+        println!("Rows with equal values in the chosen columns form one group\n");
+
+        let mut select = Select::with_theme(&app.view.dialog_theme);
+        select.default(last_chosen);
+        for column in columns {
+            select.item(format!("[{}] {}", if report.group_by.contains(column) { "x" } else { " " }, column).as_str());
+        }
+        select.item("Done");
+        last_chosen = select.interact()?;
+
+        match columns.get(last_chosen) {
+            Some(column) if report.group_by.contains(column) => report.group_by.retain(|chosen| chosen != column),
+            Some(column) => report.group_by.push(column.clone()),
+            None => break,
+        }
+    }
+
+    report.forget_missing_labels();
+    Ok(())
+}
+
+fn add_aggregate(app: &App, columns: &[String], report: &mut Report) -> Result<()> {
+    clear()?;
+    println!("Add aggregate computed for every group\n");
+
+    let names: Vec<&str> = Aggregate::ALL.iter().map(|aggregate| aggregate.name()).collect();
+    let aggregate = Aggregate::ALL[Select::with_theme(&app.view.dialog_theme).with_prompt("Aggregate").items(&names).default(0).interact()?];
+
+    // COUNT(*) counts rows, with a column it skips NULLs
+    let mut choices: Vec<Option<&String>> = columns.iter().map(Some).collect();
+    if aggregate == Aggregate::Count {
+        choices.insert(0, None);
+    }
+    let items: Vec<&str> = choices.iter().map(|column| column.map_or("* (every row)", String::as_str)).collect();
+    let column = choices[Select::with_theme(&app.view.dialog_theme).with_prompt("Column").items(&items).default(0).interact()?].cloned();
+
+    if !report.aggregates.contains(&(aggregate, column.clone())) {
+        report.aggregates.push((aggregate, column));
+    }
+
+    Ok(())
+}
+
+fn set_having(app: &App, table: &str, report: &mut Report) -> Result<()> {
+    clear()?;
+
+    println!("Define condition on which groups are shown, empty shows every group");
+    println!("Report columns can be used by their names, e.g. \"count(*)\" > 1");
+    if let Some(having) = &report.having {
+        println!("Current: HAVING {}", having);
+    }
+    println!("Tab completes column names and keywords\n");
+
+    let mut words: Vec<String> = report.labels().iter().map(|label| quote_identifier(label)).collect();
+    words.extend(CONDITION_KEYWORDS.iter().map(|keyword| keyword.to_string()));
+
+    let having: String = Input::with_theme(&app.view.dialog_theme)
+        .with_prompt("HAVING")
+        .allow_empty(true)
+        .word_completions(words)
+        .interact()?;
+
+    let previous = std::mem::replace(&mut report.having, Some(having.trim().to_string()).filter(|having| !having.is_empty()));
+
+    // Mistakes are shown here instead of leaving the report
+    let sql = report.sql(table);
+    if let Err(err) = db::fetch(app.require_connection()?, &sql, params![1, 0]) {
+        print_error(&err);
+        wait_for_keypress();
+        report.having = previous;
+    }
+
+    Ok(())
+}
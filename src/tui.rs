@@ -0,0 +1,620 @@
+// Full-screen alternative to the menus, started with --tui. Tables are listed in a sidebar and the
+// chosen one is shown in a grid which scrolls in both directions. Cells can be edited in place,
+// every change goes through db::update and the same confirmation policy as the menus. BLOB cells
+// can be loaded from and saved to files.
+
+use std::io::{self, Stdout};
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use rusqlite::types::Value;
+
+use crate::app::App;
+use crate::blob;
+use crate::db::{self, Column, Query};
+use crate::display::value_repr;
+use crate::error::{Error, Result};
+use crate::history::record_query;
+use crate::utils::{format_size, needs_confirmation, truncate};
+use crate::value_parser::{Affinity, parse_value, to_input};
+
+const HELP: &str = "Tab focus  Enter/e edit  l/s load/save file  t begin  c commit  r rollback  q quit";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Sidebar,
+    Grid,
+}
+
+// What the text typed in the status bar is for
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Value,
+    LoadFile,
+    SaveFile,
+}
+
+// Change waiting for y/n
+enum Pending {
+    Update(Query),
+    LoadFile(PathBuf),
+}
+
+struct State {
+    tables: Vec<String>,
+    selected_table: usize,
+    focus: Focus,
+
+    columns: Vec<Column>,
+    // Rowid is missing for WITHOUT ROWID tables, those are shown but cannot be edited
+    rows: Vec<(Option<i64>, Vec<Value>)>,
+    row_count: usize,
+
+    // Cursor is an absolute row index, rows hold the window starting at row_offset
+    cursor_row: usize,
+    cursor_column: usize,
+    row_offset: usize,
+    column_offset: usize,
+    // Rows which fit in the grid during the last draw
+    visible_rows: usize,
+
+    editing: Option<(Prompt, String)>,
+    pending: Option<Pending>,
+    leaving: bool,
+    message: Option<String>,
+}
+
+pub fn run(app: &mut App, path_text: &str) -> Result<()> {
+    let mut state = State {
+        tables: db::table_names(app.require_connection()?)?,
+        selected_table: 0,
+        focus: Focus::Sidebar,
+        columns: Vec::new(),
+        rows: Vec::new(),
+        row_count: 0,
+        cursor_row: 0,
+        cursor_column: 0,
+        row_offset: 0,
+        column_offset: 0,
+        visible_rows: 1,
+        editing: None,
+        pending: None,
+        leaving: false,
+        message: None,
+    };
+
+    // Start on the table chosen earlier, if there is one
+    if let Some(active) = app.active_table() {
+        if let Some(position) = state.tables.iter().position(|table| table == active) {
+            state.selected_table = position;
+        }
+    }
+    if !state.tables.is_empty() {
+        load_table(app, &mut state)?;
+    }
+
+    let mut screen = Screen::enter()?;
+
+    loop {
+        screen.terminal.draw(|frame| draw(frame, app, &mut state, path_text))?;
+        // The window may have changed size, keep the cursor inside it
+        if scroll_to_cursor(&mut state) {
+            load_rows(app, &mut state)?;
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+
+        state.message = None;
+        if !handle_key(app, &mut state, key)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Raw mode and the alternate screen are left even when the TUI returns with an error
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+
+        let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        Ok(Screen { terminal })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Returns false when the TUI should be closed
+fn handle_key(app: &mut App, state: &mut State, key: KeyEvent) -> Result<bool> {
+    let leaving = state.leaving;
+    state.leaving = false;
+
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        state.pending = None;
+        state.editing = None;
+        return quit(app, state, leaving);
+    }
+
+    if let Some(pending) = state.pending.take() {
+        if let KeyCode::Char('y') | KeyCode::Char('Y') = key.code {
+            match pending {
+                Pending::Update(query) => execute_update(app, state, query)?,
+                Pending::LoadFile(path) => load_file(app, state, &path)?,
+            }
+        } else {
+            state.message = Some(String::from("Change discarded"));
+        }
+        return Ok(true);
+    }
+
+    if state.editing.is_some() {
+        edit_key(app, state, key.code)?;
+        return Ok(true);
+    }
+
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => return quit(app, state, leaving),
+        KeyCode::Tab | KeyCode::BackTab => {
+            state.focus = match state.focus {
+                Focus::Sidebar if !state.columns.is_empty() => Focus::Grid,
+                _ => Focus::Sidebar,
+            };
+        },
+        KeyCode::Char('t') => run_statement(app, state, "BEGIN", "Transaction started"),
+        KeyCode::Char('c') => run_statement(app, state, "COMMIT", "Transaction committed"),
+        KeyCode::Char('r') => {
+            run_statement(app, state, "ROLLBACK", "Transaction rolled back");
+            reload(app, state)?;
+        },
+        code => match state.focus {
+            Focus::Sidebar => sidebar_key(app, state, code)?,
+            Focus::Grid => grid_key(app, state, code)?,
+        },
+    }
+
+    Ok(true)
+}
+
+fn sidebar_key(app: &mut App, state: &mut State, code: KeyCode) -> Result<()> {
+    match code {
+        KeyCode::Up => { state.selected_table = state.selected_table.saturating_sub(1); },
+        KeyCode::Down if state.selected_table + 1 < state.tables.len() => { state.selected_table += 1; },
+        KeyCode::Enter | KeyCode::Right if !state.tables.is_empty() => {
+            load_table(app, state)?;
+            state.focus = Focus::Grid;
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+fn grid_key(app: &mut App, state: &mut State, code: KeyCode) -> Result<()> {
+    let last_row = state.row_count.saturating_sub(1);
+    let last_column = state.columns.len().saturating_sub(1);
+    let page = state.visible_rows.max(1);
+
+    match code {
+        KeyCode::Up => { state.cursor_row = state.cursor_row.saturating_sub(1); },
+        KeyCode::Down => { state.cursor_row = (state.cursor_row + 1).min(last_row); },
+        KeyCode::PageUp => { state.cursor_row = state.cursor_row.saturating_sub(page); },
+        KeyCode::PageDown => { state.cursor_row = (state.cursor_row + page).min(last_row); },
+        KeyCode::Home => { state.cursor_row = 0; },
+        KeyCode::End => { state.cursor_row = last_row; },
+        KeyCode::Left if state.cursor_column == 0 => { state.focus = Focus::Sidebar; },
+        KeyCode::Left => { state.cursor_column -= 1; },
+        KeyCode::Right => { state.cursor_column = (state.cursor_column + 1).min(last_column); },
+        KeyCode::Enter | KeyCode::Char('e') => start_editing(app, state),
+        KeyCode::Char('l') => start_loading(app, state),
+        KeyCode::Char('s') => start_saving(state),
+        _ => {},
+    }
+
+    if scroll_to_cursor(state) {
+        load_rows(app, state)?;
+    }
+
+    Ok(())
+}
+
+// Open transaction would be rolled back silently once the connection is closed, so quitting
+// with one asks to repeat the key
+fn quit(app: &App, state: &mut State, leaving: bool) -> Result<bool> {
+    if leaving || app.require_connection()?.is_autocommit() {
+        return Ok(false);
+    }
+
+    state.leaving = true;
+    state.message = Some(String::from("Transaction is still open, press q or Ctrl-C again to roll it back and quit"));
+    Ok(true)
+}
+
+fn start_editing(app: &App, state: &mut State) {
+    if app.is_read_only() {
+        state.message = Some(String::from("Database is opened in read-only mode"));
+        return;
+    }
+
+    let value = match current_cell(state) {
+        Some((_, value)) => value,
+        None => return,
+    };
+
+    let affinity = column_affinity(state);
+    // Saving the prefilled text must not change the value, e.g. turn a BLOB into TEXT
+    match to_input(&value, affinity) {
+        Some(text) => state.editing = Some((Prompt::Value, text)),
+        None => state.message = Some(format!("Value cannot be typed with {} affinity, load a file instead", affinity.name())),
+    }
+}
+
+fn start_loading(app: &App, state: &mut State) {
+    if app.is_read_only() {
+        state.message = Some(String::from("Database is opened in read-only mode"));
+        return;
+    }
+
+    if current_cell(state).is_some() {
+        state.editing = Some((Prompt::LoadFile, String::new()));
+    }
+}
+
+// Suggests a file name from the column, row and the type of the data
+fn start_saving(state: &mut State) {
+    let (rowid, file_type) = match current_cell(state) {
+        Some((rowid, Value::Blob(bytes))) => (rowid, blob::sniff(&bytes)),
+        Some((rowid, Value::Text(text))) => (rowid, blob::sniff(text.as_bytes())),
+        Some(_) => {
+            state.message = Some(String::from("Only text and BLOB cells can be saved to a file"));
+            return;
+        },
+        None => return,
+    };
+
+    let name = format!("{}_{}.{}", state.columns[state.cursor_column].name, rowid, file_type.extension);
+    state.editing = Some((Prompt::SaveFile, name));
+}
+
+// Rowid and value under the cursor, None with a message for tables without rowid
+fn current_cell(state: &mut State) -> Option<(i64, Value)> {
+    let (rowid, values) = state.rows.get(state.cursor_row - state.row_offset)?;
+
+    match rowid {
+        Some(rowid) => Some((*rowid, values[state.cursor_column].clone())),
+        None => {
+            state.message = Some(String::from("Rows of this table have no rowid and cannot be edited here"));
+            None
+        },
+    }
+}
+
+fn edit_key(app: &mut App, state: &mut State, code: KeyCode) -> Result<()> {
+    let affinity = column_affinity(state);
+    let (prompt, buffer) = match state.editing.as_mut() {
+        Some((prompt, buffer)) => (*prompt, buffer),
+        None => return Ok(()),
+    };
+
+    match code {
+        KeyCode::Esc => { state.editing = None; },
+        KeyCode::Backspace => { buffer.pop(); },
+        KeyCode::Char(c) => { buffer.push(c); },
+        KeyCode::Enter if prompt != Prompt::Value => {
+            let path = PathBuf::from(buffer.trim());
+            state.editing = None;
+
+            if path.as_os_str().is_empty() {
+                state.message = Some(String::from("No file given"));
+            } else if prompt == Prompt::SaveFile {
+                save_file(app, state, &path)?;
+            } else {
+                let description = format!(
+                    "UPDATE {} SET {} = contents of '{}' WHERE rowid = {}",
+                    db::quote_identifier(&state.tables[state.selected_table]),
+                    db::quote_identifier(&state.columns[state.cursor_column].name),
+                    path.display(),
+                    state.rows[state.cursor_row - state.row_offset].0.unwrap_or_default(),
+                );
+
+                if needs_confirmation(app, &description) {
+                    state.message = Some(format!("{}  Execute? (y/n)", description));
+                    state.pending = Some(Pending::LoadFile(path));
+                } else {
+                    load_file(app, state, &path)?;
+                }
+            }
+        },
+        KeyCode::Enter => {
+            let value = match parse_value(buffer, affinity) {
+                Ok(value) => value,
+                Err(cause) => {
+                    state.message = Some(cause);
+                    return Ok(());
+                },
+            };
+            state.editing = None;
+
+            let rowid = match state.rows[state.cursor_row - state.row_offset].0 {
+                Some(rowid) => rowid,
+                None => return Ok(()),
+            };
+            let table = &state.tables[state.selected_table];
+            let column = state.columns[state.cursor_column].name.clone();
+            let query = db::update(table, vec![(column, value)], Some(&format!("rowid = {}", rowid)));
+
+            if needs_confirmation(app, &query.sql) {
+                state.message = Some(format!("{}  Execute? (y/n)", query.preview()));
+                state.pending = Some(Pending::Update(query));
+            } else {
+                execute_update(app, state, query)?;
+            }
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+// Failed statements are reported in the status bar, the grid stays open
+fn execute_update(app: &mut App, state: &mut State, query: Query) -> Result<()> {
+    match query.execute(app.require_connection()?) {
+        Ok(_) => {
+            record_query(app, &query.preview());
+            state.message = Some(String::from("Row updated"));
+        },
+        Err(err) => { state.message = Some(err.to_string()); },
+    }
+
+    load_rows(app, state)
+}
+
+fn load_file(app: &App, state: &mut State, path: &Path) -> Result<()> {
+    let rowid = match state.rows[state.cursor_row - state.row_offset].0 {
+        Some(rowid) => rowid,
+        None => return Ok(()),
+    };
+    let table = &state.tables[state.selected_table];
+    let column = &state.columns[state.cursor_column].name;
+
+    state.message = Some(match blob::load_file(app.require_connection()?, table, column, rowid, path) {
+        Ok(size) => format!("Loaded {} from {}", format_size(size), path.display()),
+        Err(err) => err.to_string(),
+    });
+
+    load_rows(app, state)
+}
+
+fn save_file(app: &App, state: &mut State, path: &Path) -> Result<()> {
+    let (rowid, value) = match current_cell(state) {
+        Some(cell) => cell,
+        None => return Ok(()),
+    };
+    let table = &state.tables[state.selected_table];
+    let column = &state.columns[state.cursor_column].name;
+
+    state.message = Some(match blob::save_file(app.require_connection()?, table, column, rowid, path) {
+        Ok(size) => {
+            let mime = match &value {
+                Value::Blob(bytes) => blob::sniff(bytes).mime,
+                _ => "text/plain",
+            };
+            format!("Saved {} of {} to {}", format_size(size), mime, path.display())
+        },
+        Err(err) => err.to_string(),
+    });
+
+    Ok(())
+}
+
+fn run_statement(app: &App, state: &mut State, sql: &str, done: &str) {
+    let result = app.require_connection()
+        .and_then(|connection| connection.execute_batch(sql).map_err(|err| Error::in_statement(err, sql)));
+
+    state.message = Some(match result {
+        Ok(()) => String::from(done),
+        Err(err) => err.to_string(),
+    });
+}
+
+fn load_table(app: &mut App, state: &mut State) -> Result<()> {
+    let table = state.tables[state.selected_table].clone();
+    app.set_active_table(&table)?;
+
+    state.columns = db::table_columns(app.require_connection()?, &table)?;
+    state.cursor_row = 0;
+    state.cursor_column = 0;
+    state.row_offset = 0;
+    state.column_offset = 0;
+
+    reload(app, state)
+}
+
+fn reload(app: &App, state: &mut State) -> Result<()> {
+    if state.columns.is_empty() {
+        return Ok(());
+    }
+
+    state.row_count = db::count_rows(app.require_connection()?, &state.tables[state.selected_table])?;
+    state.cursor_row = state.cursor_row.min(state.row_count.saturating_sub(1));
+    scroll_to_cursor(state);
+    load_rows(app, state)
+}
+
+fn load_rows(app: &App, state: &mut State) -> Result<()> {
+    let connection = app.require_connection()?;
+    let table = &state.tables[state.selected_table];
+    let limit = state.visible_rows.max(1);
+
+    state.rows = match db::fetch_range_with_rowid(connection, table, state.row_offset, limit) {
+        Ok(rows) => rows.into_iter().map(|(rowid, values)| (Some(rowid), values)).collect(),
+        Err(_) => db::fetch_range(connection, table, &[], state.row_offset, limit)?
+            .rows.into_iter().map(|values| (None, values)).collect(),
+    };
+
+    Ok(())
+}
+
+// Moves the row window so the cursor is visible, true when other rows have to be loaded
+fn scroll_to_cursor(state: &mut State) -> bool {
+    let visible = state.visible_rows.max(1);
+    let previous = state.row_offset;
+
+    if state.cursor_row < state.row_offset {
+        state.row_offset = state.cursor_row;
+    } else if state.cursor_row >= state.row_offset + visible {
+        state.row_offset = state.cursor_row + 1 - visible;
+    }
+
+    if state.cursor_column < state.column_offset {
+        state.column_offset = state.cursor_column;
+    }
+
+    previous != state.row_offset || (state.rows.len() < visible && state.row_offset + state.rows.len() < state.row_count)
+}
+
+fn column_affinity(state: &State) -> Affinity {
+    Affinity::from_sql_type(&state.columns[state.cursor_column].sqltype)
+}
+
+fn draw(frame: &mut Frame, app: &App, state: &mut State, path_text: &str) {
+    let [main, status, help] = split(Direction::Vertical, frame.size(), [Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)]);
+    let [sidebar, grid] = split(Direction::Horizontal, main, [Constraint::Length(sidebar_width(state)), Constraint::Min(10)]);
+
+    let focused = Style::default().fg(Color::Cyan);
+    let border = |focus| if state.focus == focus { focused } else { Style::default() };
+
+    let items: Vec<ListItem> = state.tables.iter().map(|table| ListItem::new(table.as_str())).collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Tables").border_style(border(Focus::Sidebar)))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(Some(state.selected_table).filter(|_| !state.tables.is_empty()));
+    frame.render_stateful_widget(list, sidebar, &mut list_state);
+
+    let title = match state.columns.is_empty() {
+        true => String::from("No table"),
+        false => format!("{} ({} rows)", state.tables[state.selected_table], state.row_count),
+    };
+    let block = Block::default().borders(Borders::ALL).title(title).border_style(border(Focus::Grid));
+    let inner = block.inner(grid);
+    frame.render_widget(block, grid);
+
+    // One line goes to the header
+    state.visible_rows = (inner.height as usize).saturating_sub(1).max(1);
+    draw_grid(frame, app, state, inner);
+
+    let transaction = match app.connection.as_ref().map(|connection| connection.is_autocommit()) {
+        Some(false) => Span::styled(" transaction open ", Style::default().fg(Color::Black).bg(Color::Yellow)),
+        _ => Span::raw(" autocommit "),
+    };
+    let path_style = Style::default().fg(if app.is_in_memory() { Color::Red } else { Color::Green });
+    let mut spans = vec![Span::styled(path_text, path_style), Span::raw(" |"), transaction, Span::raw("| ")];
+    if app.is_read_only() {
+        spans.insert(2, Span::styled(" read-only ", Style::default().fg(Color::Black).bg(Color::Red)));
+        spans.insert(3, Span::raw("|"));
+    }
+
+    match (&state.editing, &state.message) {
+        (Some((prompt, buffer)), _) => {
+            let column = &state.columns[state.cursor_column].name;
+            let prompt = match prompt {
+                Prompt::Value => format!("Edit {}: ", column),
+                Prompt::LoadFile => format!("Load file into {}: ", column),
+                Prompt::SaveFile => format!("Save {} to file: ", column),
+            };
+            let x = status.x + spans.iter().map(|span| span.width()).sum::<usize>() as u16 + (prompt.chars().count() + buffer.chars().count()) as u16;
+            frame.set_cursor(x.min(status.right().saturating_sub(1)), status.y);
+            spans.push(Span::styled(prompt, Style::default().add_modifier(Modifier::BOLD)));
+            spans.push(Span::raw(buffer.as_str()));
+        },
+        (None, Some(message)) => spans.push(Span::raw(message.as_str())),
+        (None, None) => {},
+    }
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), status);
+    frame.render_widget(Paragraph::new(HELP).style(Style::default().add_modifier(Modifier::DIM)), help);
+}
+
+// Columns are drawn from column_offset until the width runs out, the offset moves right until the
+// cursor column fits.
+fn draw_grid(frame: &mut Frame, app: &App, state: &mut State, area: Rect) {
+    if state.columns.is_empty() {
+        return;
+    }
+
+    let widths: Vec<u16> = state.columns.iter().enumerate()
+        .map(|(i, column)| {
+            let values = state.rows.iter().map(|(_, values)| value_repr(&app.config, &values[i]).chars().count());
+            let header = column.name.chars().count().min(app.config.truncate_width);
+            values.fold(header, usize::max).max(1) as u16
+        })
+        .collect();
+
+    let fits = |offset: usize, last: usize| widths[offset..=last].iter().map(|width| width + 1).sum::<u16>() <= area.width.max(1);
+    while state.column_offset < state.cursor_column && !fits(state.column_offset, state.cursor_column) {
+        state.column_offset += 1;
+    }
+
+    let mut shown = Vec::new();
+    let mut used = 0;
+    for (i, width) in widths.iter().enumerate().skip(state.column_offset) {
+        if used + width > area.width && !shown.is_empty() {
+            break;
+        }
+        shown.push(i);
+        used += width + 1;
+    }
+
+    let header = Row::new(shown.iter().map(|&i| truncate(&state.columns[i].name, app.config.truncate_width).to_string()))
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+
+    let cursor = Style::default().add_modifier(Modifier::REVERSED);
+    let rows: Vec<Row> = state.rows.iter().enumerate()
+        .map(|(n, (_, values))| {
+            let current = state.row_offset + n == state.cursor_row;
+            Row::new(shown.iter().map(|&i| {
+                let cell = ratatui::widgets::Cell::from(value_repr(&app.config, &values[i]));
+                if current && i == state.cursor_column && state.focus == Focus::Grid { cell.style(cursor) } else { cell }
+            }))
+        })
+        .collect();
+
+    let table = Table::new(rows, shown.iter().map(|&i| Constraint::Length(widths[i])))
+        .header(header)
+        .column_spacing(1);
+    frame.render_widget(table, area);
+}
+
+fn sidebar_width(state: &State) -> u16 {
+    let longest = state.tables.iter().map(|table| table.chars().count()).max().unwrap_or(0);
+    (longest.clamp(6, 30) + 2) as u16
+}
+
+fn split<const N: usize>(direction: Direction, area: Rect, constraints: [Constraint; N]) -> [Rect; N] {
+    let chunks = Layout::default().direction(direction).constraints(constraints).split(area);
+    let mut areas = [Rect::default(); N];
+    areas.copy_from_slice(&chunks);
+    areas
+}
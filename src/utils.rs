@@ -1,39 +1,47 @@
-use console::Term;
+use console::{style, Term};
 use crate::app::App;
-use dialoguer::Confirmation;
+use crate::config::ConfirmationPolicy;
+use crate::error::{Error, Result};
+use crate::prompt::{Confirmation, read_key};
+use crate::sql::pretty;
 
-use rusqlite::types::Value;
-
-pub fn clear() {
-    Term::stdout().clear_screen().expect("IO error");
+pub fn clear() -> Result<()> {
+    Term::stdout().clear_screen()?;
+    Ok(())
 }
 
 pub fn wait_for_keypress() {
-    use std::io;
-    use std::io::prelude::*;
-
     println!("Press any key to continue...");
-    io::stdin().read(&mut [0u8]).unwrap();
+    // ESC or Ctrl-C would only lead back to the same place
+    let _ = read_key();
 }
 
-pub fn validate_column_name(input: &str) -> bool {
-    use regex::Regex;
+pub fn print_error(err: &Error) {
+    println!("{}: {}\n", style("Error").red(), err);
+}
 
-    lazy_static! {
-        static ref COLUMN_REGEX: Regex = Regex::new("^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+// Any text is a valid name once quoted, only NUL cannot be stored in the schema
+pub fn validate_column_name(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        return Err(String::from("Name cannot be empty"));
+    }
+
+    if input.contains('\0') {
+        return Err(String::from("Name cannot contain NUL character"));
     }
 
-    COLUMN_REGEX.is_match(input)
+    Ok(())
 }
 
-pub fn validate_table_name(input: &str) -> bool {
-    use regex::Regex;
+pub fn validate_table_name(input: &str) -> Result<(), String> {
+    validate_column_name(input)?;
 
-    lazy_static! {
-        static ref TABLE_REGEX: Regex = Regex::new("^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+    // sqlite refuses to create tables with this prefix
+    if input.to_lowercase().starts_with("sqlite_") {
+        return Err(String::from("Names starting with sqlite_ are reserved for internal use"));
     }
 
-    TABLE_REGEX.is_match(input)
+    Ok(())
 }
 
 pub fn validate_sql_type(input: &str) -> bool {
@@ -46,6 +54,52 @@ pub fn validate_sql_type(input: &str) -> bool {
     TYPE_REGEX.is_match(input)
 }
 
+/// Words completed in WHERE conditions next to column names.
+pub const CONDITION_KEYWORDS: [&str; 17] = [
+    "AND", "OR", "NOT", "IS", "NULL", "LIKE", "GLOB", "IN", "BETWEEN", "EXISTS",
+    "SELECT", "FROM", "WHERE", "CASE", "WHEN", "THEN", "END",
+];
+
+/// ` Did you mean 'a' or 'b'?` naming the candidates closest to a misspelled word, empty when
+/// none is close enough.
+pub fn did_you_mean<'a, I>(word: &str, candidates: I) -> String
+    where I: IntoIterator<Item = &'a str>
+{
+    let word = word.to_lowercase();
+    // A typo every three characters is still recognizable
+    let max_distance = (word.chars().count() / 3).max(1);
+
+    let mut close: Vec<(usize, &str)> = candidates.into_iter()
+        .map(|candidate| (edit_distance(&word, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close.sort();
+
+    let names: Vec<String> = close.iter().take(3).map(|(_, name)| format!("'{}'", name)).collect();
+    match names.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(" Did you mean {}?", last),
+        Some((last, rest)) => format!(" Did you mean {} or {}?", rest.join(", "), last),
+    }
+}
+
+// Levenshtein distance counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 #[derive(Debug)]
 pub struct ValidationError {
     cause: String,
@@ -54,7 +108,7 @@ pub struct ValidationError {
 impl ValidationError {
     pub fn new(cause: String) -> Self {
         Self {
-            cause: cause
+            cause
         }
     }
 }
@@ -65,16 +119,25 @@ impl std::fmt::Display for ValidationError {
     }
 }
 
+type ValidateFunction = dyn Fn(&str) -> Result<(), String>;
+
 pub struct ValidatorAdaptor {
-    validate_function: fn(&str) -> bool,
-    error_reason: String,
+    validate_function: Box<ValidateFunction>,
 }
 
 impl ValidatorAdaptor {
     pub fn new(validate_function: fn(&str)->bool, error_reason: String) -> Self {
+        Self::with_reason(move |text| {
+            if validate_function(text) { Ok(()) } else { Err(error_reason.clone()) }
+        })
+    }
+
+    // For validators which can tell what exactly is wrong with the input
+    pub fn with_reason<F>(validate_function: F) -> Self
+        where F: Fn(&str) -> Result<(), String> + 'static
+    {
         Self {
-            validate_function: validate_function,
-            error_reason: error_reason,
+            validate_function: Box::new(validate_function),
         }
     }
 }
@@ -83,22 +146,58 @@ impl dialoguer::Validator for ValidatorAdaptor {
     type Err = ValidationError;
 
     fn validate(&self, text: &str) -> Result<(), ValidationError > {
-        if (self.validate_function)(text) {
-            Ok(())
-        } else {
-            Err(ValidationError::new(self.error_reason.clone()))
-        }
+        (self.validate_function)(text).map_err(ValidationError::new)
     }
 }
 
 
-pub fn ask_for_confirmation_before_query(app: &App, query: &String) -> bool{
+pub fn ask_for_confirmation_before_query(app: &App, query: &str) -> Result<bool> {
+    if !needs_confirmation(app, query) {
+        println!("Executing query:");
+        println!("{}\n", pretty(&app.view.sql_style, query));
+        return Ok(true);
+    }
+
     println!("You are about to execute following query:");
-    println!("{}\n", query);
+    println!("{}\n", pretty(&app.view.sql_style, query));
 
-    Confirmation::with_theme(&app.view.dialog_theme).with_text("Do you proceed?").interact().expect("IO error")
+    Confirmation::with_theme(&app.view.dialog_theme).with_text("Do you proceed?").interact()
 }
 
+pub fn needs_confirmation(app: &App, query: &str) -> bool {
+    match app.config.confirmation {
+        ConfirmationPolicy::Always => true,
+        ConfirmationPolicy::Destructive => is_destructive(query),
+        ConfirmationPolicy::Never => false,
+    }
+}
+
+// Statements which may lose data, the rest only adds rows or objects
+pub fn is_destructive(query: &str) -> bool {
+    const DESTRUCTIVE: [&str; 5] = ["DELETE", "UPDATE", "DROP", "ALTER", "REPLACE"];
+
+    let first_word = query.split_whitespace().next().unwrap_or("").to_uppercase();
+    DESTRUCTIVE.contains(&first_word.as_str()) || query.to_uppercase().contains("OR REPLACE")
+}
+
+
+/// Byte count with a binary unit, like 12.5 KiB.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
 
 // Taken from SO, credit to Shepmaster, "How i can truncate a string to have at most N characters
 pub fn truncate(text: &str, count: usize) -> &str {
@@ -108,14 +207,60 @@ pub fn truncate(text: &str, count: usize) -> &str {
     }
 }
 
-// pub fn to_sqlite_literal(val: &Value) -> String {
-//     use rusqlite::types::Value::*;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_close_names() {
+        let tables = ["users", "orders", "order_items"];
 
-//     match val {
-//         Null => format!("NULL"),
-//         Integer(i) => String::from(i),
-//         Real(f) => Real(f+1.0),
-//         Text(t) => Text({ let mut c = t.clone(); c.push('a'); c }),
-//         Blob(v) => Blob(v.clone()),
-//     }
-// }
+        assert_eq!(did_you_mean("userz", tables.iter().copied()), " Did you mean 'users'?");
+        assert_eq!(did_you_mean("ORDERS", tables.iter().copied()), " Did you mean 'orders'?");
+        assert_eq!(did_you_mean("products", tables.iter().copied()), "");
+    }
+
+    #[test]
+    fn lists_several_suggestions_closest_first() {
+        let names = ["cart", "card", "care", "carton"];
+        assert_eq!(did_you_mean("car", names.iter().copied()), " Did you mean 'card', 'care' or 'cart'?");
+    }
+
+    #[test]
+    fn edit_distance_counts_characters() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("zażółć", "zazółć"), 1);
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert!(validate_table_name("my table").is_ok());
+        assert!(validate_table_name(" ").is_err());
+        assert!(validate_table_name("sqlite_stat1").is_err());
+        assert!(validate_column_name("a\0b").is_err());
+        assert!(validate_sql_type("VARCHAR"));
+        assert!(!validate_sql_type("VARCHAR(10)"));
+    }
+
+    #[test]
+    fn destructive_statements() {
+        assert!(is_destructive("delete from t"));
+        assert!(is_destructive("INSERT OR REPLACE INTO t VALUES (1)"));
+        assert!(!is_destructive("INSERT INTO t VALUES (1)"));
+        assert!(!is_destructive("SELECT * FROM t"));
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn truncate_counts_characters() {
+        assert_eq!(truncate("zażółć", 3), "zaż");
+        assert_eq!(truncate("ab", 3), "ab");
+    }
+}
@@ -0,0 +1,341 @@
+// Turns user input into sqlite values according to the column affinity.
+//
+// Accepted forms:
+//   empty or NULL        -> NULL
+//   'quoted text'        -> TEXT for any column, '' escapes a quote
+//   1_000, 0xFF, -12     -> INTEGER
+//   1.5, 1_000.25, 2e10  -> REAL
+//   x:00ff               -> BLOB from hex digits
+//   @path/to/file        -> BLOB with file contents
+// Columns with TEXT affinity take anything else verbatim, without quoting.
+
+use rusqlite::types::Value;
+
+use crate::db::to_sqlite_literal;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    // Rules from "Determination Of Column Affinity" in sqlite docs, checked in the same order.
+    pub fn from_sql_type(sql_type: &str) -> Self {
+        let sql_type = sql_type.to_uppercase();
+
+        if sql_type.contains("INT") {
+            Affinity::Integer
+        } else if sql_type.contains("CHAR") || sql_type.contains("CLOB") || sql_type.contains("TEXT") {
+            Affinity::Text
+        } else if sql_type.contains("BLOB") || sql_type.trim().is_empty() {
+            Affinity::Blob
+        } else if sql_type.contains("REAL") || sql_type.contains("FLOA") || sql_type.contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Affinity::Integer => "INTEGER",
+            Affinity::Text => "TEXT",
+            Affinity::Blob => "BLOB",
+            Affinity::Real => "REAL",
+            Affinity::Numeric => "NUMERIC",
+        }
+    }
+}
+
+pub fn parse_value(input: &str, affinity: Affinity) -> Result<Value, String> {
+    let trimmed = input.trim();
+
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("NULL") {
+        return Ok(Value::Null);
+    }
+
+    if let Some(text) = parse_quoted(trimmed) {
+        return text.map(Value::Text);
+    }
+
+    if affinity != Affinity::Text {
+        if let Some(blob) = parse_blob(trimmed) {
+            return blob.map(Value::Blob);
+        }
+    }
+
+    match affinity {
+        Affinity::Text => Ok(Value::Text(input.to_string())),
+        Affinity::Integer => parse_integer(trimmed)
+            .or_else(|| parse_real(trimmed))
+            .ok_or_else(|| format!("'{}' is not an integer", trimmed)),
+        Affinity::Real => parse_real(trimmed)
+            .ok_or_else(|| format!("'{}' is not a real number", trimmed)),
+        Affinity::Numeric => parse_integer(trimmed)
+            .or_else(|| parse_real(trimmed))
+            .ok_or_else(|| format!("'{}' is not a number, quote it to store text", trimmed)),
+        Affinity::Blob => Ok(parse_integer(trimmed)
+            .or_else(|| parse_real(trimmed))
+            .unwrap_or_else(|| Value::Text(input.to_string()))),
+    }
+}
+
+/// Input which `parse_value` reads back as the same value, used to prefill edited values. None
+/// when the affinity has no such form, like a BLOB in a TEXT column.
+pub fn to_input(value: &Value, affinity: Affinity) -> Option<String> {
+    let candidates = match value {
+        Value::Null => vec![String::new()],
+        // Text which the parser would read differently, like '' or NULL, falls back to a quoted literal
+        Value::Text(text) => vec![text.clone(), to_sqlite_literal(value)],
+        Value::Blob(bytes) => vec![format!("x:{}", bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())],
+        value => vec![to_sqlite_literal(value)],
+    };
+
+    candidates.into_iter().find(|input| parse_value(input, affinity).as_ref() == Ok(value))
+}
+
+/// Same checks as `parse_value`, except that files are only checked to be readable. Validators run
+/// on every submitted input, the file is read once by `parse_value` after it is accepted.
+pub fn validate_value(input: &str, affinity: Affinity) -> Result<(), String> {
+    match input.trim().strip_prefix('@') {
+        Some(path) if affinity != Affinity::Text => check_file(path),
+        _ => parse_value(input, affinity).map(|_| ()),
+    }
+}
+
+// Devices and pipes like /dev/zero could be read forever
+fn check_file(path: &str) -> Result<(), String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => Ok(()),
+        Ok(_) => Err(format!("'{}' is not a regular file", path)),
+        Err(err) => Err(format!("Could not read '{}'. Error: {}", path, err)),
+    }
+}
+
+fn parse_quoted(input: &str) -> Option<Result<String, String>> {
+    if input.len() < 2 || !input.starts_with('\'') || !input.ends_with('\'') {
+        return None;
+    }
+
+    let inner = &input[1..input.len()-1];
+    if inner.replace("''", "").contains('\'') {
+        return Some(Err(String::from("Quotes inside quoted text must be doubled")));
+    }
+
+    Some(Ok(inner.replace("''", "'")))
+}
+
+fn parse_blob(input: &str) -> Option<Result<Vec<u8>, String>> {
+    if let Some(path) = input.strip_prefix('@') {
+        return Some(check_file(path).and_then(|_| {
+            std::fs::read(path).map_err(|err| format!("Could not read '{}'. Error: {}", path, err))
+        }));
+    }
+
+    let hex = input.strip_prefix("x:").or_else(|| input.strip_prefix("X:"))?;
+
+    if hex.len() % 2 != 0 {
+        return Some(Err(String::from("Hex blob must have an even number of digits")));
+    }
+
+    let bytes = (0..hex.len()).step_by(2)
+        .map(|i| {
+            hex.get(i..i+2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("'{}' is not a hex number", hex))
+        })
+        .collect();
+
+    Some(bytes)
+}
+
+fn strip_underscores(input: &str) -> Option<String> {
+    if input.starts_with('_') || input.ends_with('_') || input.contains("__") {
+        return None;
+    }
+
+    Some(input.replace('_', ""))
+}
+
+fn parse_integer(input: &str) -> Option<Value> {
+    let digits = strip_underscores(input)?;
+
+    let (negative, digits) = match digits.chars().next() {
+        Some('-') => (true, &digits[1..]),
+        Some('+') => (false, &digits[1..]),
+        _ => (false, digits.as_str()),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        // sqlite reads hex literals as 64-bit two's complement, 0xFFFFFFFFFFFFFFFF is -1
+        u64::from_str_radix(hex, 16).ok()? as i64
+    } else if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse::<i64>().ok()?
+    } else {
+        return None;
+    };
+
+    Some(Value::Integer(if negative { value.wrapping_neg() } else { value }))
+}
+
+fn parse_real(input: &str) -> Option<Value> {
+    let digits = strip_underscores(input)?;
+
+    // f64 parser accepts "inf" and "NaN" which are not sqlite literals
+    if !digits.chars().any(|c| c.is_ascii_digit()) || digits.chars().any(|c| c.is_alphabetic() && c != 'e' && c != 'E') {
+        return None;
+    }
+
+    digits.parse::<f64>().ok().map(Value::Real)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+
+    #[test]
+    fn affinity_follows_sqlite_rules() {
+        assert_eq!(Affinity::from_sql_type("INTEGER"), Affinity::Integer);
+        assert_eq!(Affinity::from_sql_type("unsigned big int"), Affinity::Integer);
+        assert_eq!(Affinity::from_sql_type("VARCHAR(255)"), Affinity::Text);
+        assert_eq!(Affinity::from_sql_type("CLOB"), Affinity::Text);
+        assert_eq!(Affinity::from_sql_type("BLOB"), Affinity::Blob);
+        assert_eq!(Affinity::from_sql_type(""), Affinity::Blob);
+        assert_eq!(Affinity::from_sql_type("DOUBLE PRECISION"), Affinity::Real);
+        assert_eq!(Affinity::from_sql_type("DECIMAL(10,5)"), Affinity::Numeric);
+        assert_eq!(Affinity::from_sql_type("DATETIME"), Affinity::Numeric);
+        // INT is checked first, like in sqlite
+        assert_eq!(Affinity::from_sql_type("FLOATING POINT"), Affinity::Integer);
+        assert_eq!(Affinity::from_sql_type("CHARINT"), Affinity::Integer);
+    }
+
+    #[test]
+    fn empty_and_null_are_null() {
+        for affinity in [Affinity::Integer, Affinity::Text, Affinity::Blob, Affinity::Real, Affinity::Numeric] {
+            assert_eq!(parse_value("", affinity), Ok(Value::Null));
+            assert_eq!(parse_value(" null ", affinity), Ok(Value::Null));
+        }
+    }
+
+    #[test]
+    fn quotes_force_text() {
+        assert_eq!(parse_value("'12'", Affinity::Integer), Ok(text("12")));
+        assert_eq!(parse_value("'NULL'", Affinity::Text), Ok(text("NULL")));
+        assert_eq!(parse_value("''", Affinity::Text), Ok(text("")));
+        assert_eq!(parse_value("'it''s'", Affinity::Blob), Ok(text("it's")));
+        assert!(parse_value("'it's'", Affinity::Text).is_err());
+    }
+
+    #[test]
+    fn text_affinity_takes_input_verbatim() {
+        assert_eq!(parse_value("12", Affinity::Text), Ok(text("12")));
+        assert_eq!(parse_value(" a b ", Affinity::Text), Ok(text(" a b ")));
+        assert_eq!(parse_value("@file", Affinity::Text), Ok(text("@file")));
+        assert_eq!(parse_value("x:00", Affinity::Text), Ok(text("x:00")));
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_value("1_000", Affinity::Integer), Ok(Value::Integer(1000)));
+        assert_eq!(parse_value("-12", Affinity::Integer), Ok(Value::Integer(-12)));
+        assert_eq!(parse_value("+7", Affinity::Numeric), Ok(Value::Integer(7)));
+        assert_eq!(parse_value("0xFF", Affinity::Integer), Ok(Value::Integer(255)));
+        assert_eq!(parse_value("0xFFFFFFFFFFFFFFFF", Affinity::Integer), Ok(Value::Integer(-1)));
+        assert_eq!(parse_value("1.5", Affinity::Integer), Ok(Value::Real(1.5)));
+        assert!(parse_value("1__000", Affinity::Integer).is_err());
+        assert!(parse_value("_1", Affinity::Integer).is_err());
+        assert!(parse_value("abc", Affinity::Integer).is_err());
+    }
+
+    #[test]
+    fn reals() {
+        assert_eq!(parse_value("1_000.25", Affinity::Real), Ok(Value::Real(1000.25)));
+        assert_eq!(parse_value("2e10", Affinity::Real), Ok(Value::Real(2e10)));
+        assert_eq!(parse_value("3", Affinity::Real), Ok(Value::Real(3.0)));
+        assert!(parse_value("inf", Affinity::Real).is_err());
+        assert!(parse_value("NaN", Affinity::Real).is_err());
+    }
+
+    #[test]
+    fn numeric_needs_quotes_for_text() {
+        assert_eq!(parse_value("4.5", Affinity::Numeric), Ok(Value::Real(4.5)));
+        assert!(parse_value("four", Affinity::Numeric).is_err());
+    }
+
+    #[test]
+    fn blob_affinity_keeps_numbers_and_text() {
+        assert_eq!(parse_value("12", Affinity::Blob), Ok(Value::Integer(12)));
+        assert_eq!(parse_value("abc", Affinity::Blob), Ok(text("abc")));
+    }
+
+    #[test]
+    fn hex_blobs() {
+        assert_eq!(parse_value("x:00fF", Affinity::Blob), Ok(Value::Blob(vec![0, 255])));
+        assert_eq!(parse_value("X:", Affinity::Integer), Ok(Value::Blob(Vec::new())));
+        assert!(parse_value("x:0", Affinity::Blob).is_err());
+        assert!(parse_value("x:zz", Affinity::Blob).is_err());
+    }
+
+    #[test]
+    fn files_are_read_into_blobs() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let contents = std::fs::read(path).unwrap();
+
+        assert_eq!(validate_value(&format!("@{}", path), Affinity::Blob), Ok(()));
+        assert_eq!(parse_value(&format!("@{}", path), Affinity::Blob), Ok(Value::Blob(contents)));
+    }
+
+    #[test]
+    fn only_regular_files_are_read() {
+        assert!(validate_value("@/dev/zero", Affinity::Blob).is_err());
+        assert!(parse_value("@/dev/zero", Affinity::Blob).is_err());
+        assert!(validate_value("@/nonexistent/file", Affinity::Integer).is_err());
+        assert_eq!(validate_value("@/dev/zero", Affinity::Text), Ok(()));
+    }
+
+    #[test]
+    fn blob_defaults_are_read_back() {
+        let blob = Value::Blob(vec![0x0a, 0x0b]);
+        let input = to_input(&blob, Affinity::Blob).unwrap();
+
+        assert_eq!(input, "x:0a0b");
+        assert_eq!(parse_value(&input, Affinity::Blob), Ok(blob));
+    }
+
+    #[test]
+    fn edited_values_are_read_back_for_every_affinity() {
+        let values = [
+            Value::Null, Value::Integer(-12), Value::Real(1.5), text("abc"), text("NULL"), text(""),
+            text("x:00"), text("it's"), Value::Blob(vec![0, 255]),
+        ];
+
+        for affinity in [Affinity::Integer, Affinity::Text, Affinity::Blob, Affinity::Real, Affinity::Numeric] {
+            for value in &values {
+                // sqlite converts numbers stored in these columns, so only a BLOB in a TEXT column is left out
+                let converted = match affinity {
+                    Affinity::Text => !matches!(value, Value::Null | Value::Text(_)),
+                    Affinity::Real => matches!(value, Value::Integer(_)),
+                    _ => false,
+                };
+                match to_input(value, affinity) {
+                    Some(input) => assert_eq!(parse_value(&input, affinity).as_ref(), Ok(value), "{:?} in {:?}", value, affinity),
+                    None => assert!(converted, "{:?} in {:?}", value, affinity),
+                }
+            }
+        }
+
+        assert_eq!(to_input(&Value::Blob(vec![0, 255]), Affinity::Text), None);
+        assert_eq!(to_input(&Value::Blob(vec![0, 255]), Affinity::Numeric), Some(String::from("x:00ff")));
+        assert_eq!(to_input(&text("x:00"), Affinity::Text), Some(String::from("x:00")));
+        assert_eq!(to_input(&text("x:00"), Affinity::Blob), Some(String::from("'x:00'")));
+        assert_eq!(to_input(&text("NULL"), Affinity::Text), Some(String::from("'NULL'")));
+    }
+}